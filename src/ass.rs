@@ -0,0 +1,753 @@
+use crate::structs::{Caption, CaptionStyle, Position, TextAlign};
+use crate::words;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+// CaptionStyle font sizes and offsets are measured against the default ASS
+// script resolution, so scripts authored at other resolutions are rescaled.
+pub const PLAY_RES_X: f32 = 384.0;
+pub const PLAY_RES_Y: f32 = 288.0;
+
+const MARGIN: u32 = 10;
+
+const STYLE_FORMAT: &str = "Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding";
+const EVENT_FORMAT: &str = "Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text";
+
+pub struct AssEvent {
+    pub start_ms: i32,
+    pub end_ms: i32,
+    pub text: String,
+    pub speaker: Option<String>,
+    pub style: CaptionStyle,
+}
+
+struct AssStyle {
+    style: CaptionStyle,
+    alignment: u8, // numpad layout, 1-9
+    margin_v: f32,
+}
+
+enum Section {
+    ScriptInfo,
+    Styles,
+    Events,
+    Other,
+}
+
+// Parses an ASS or SSA script into timed events, resolving each Dialogue line
+// against its named style and any inline override tags.
+pub fn parse(content: &str, base: &CaptionStyle) -> Result<Vec<AssEvent>, JsValue> {
+    let mut section = Section::Other;
+    let mut play_res_x = None;
+    let mut play_res_y = None;
+    let mut style_format: Vec<String> = Vec::new();
+    let mut event_format: Vec<String> = Vec::new();
+    let mut raw_styles: Vec<HashMap<String, String>> = Vec::new();
+    let mut raw_events: Vec<HashMap<String, String>> = Vec::new();
+    let mut legacy_alignment = false;
+
+    for line in content.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            section = match line.to_ascii_lowercase().as_str() {
+                "[script info]" => Section::ScriptInfo,
+                "[v4+ styles]" => Section::Styles,
+                "[v4 styles]" => {
+                    legacy_alignment = true;
+                    Section::Styles
+                }
+                "[events]" => Section::Events,
+                _ => Section::Other,
+            };
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+
+        match section {
+            Section::ScriptInfo => match key.trim() {
+                "PlayResX" => play_res_x = value.parse::<f32>().ok().filter(|v| *v > 0.0),
+                "PlayResY" => play_res_y = value.parse::<f32>().ok().filter(|v| *v > 0.0),
+                _ => {}
+            },
+            Section::Styles => match key.trim() {
+                "Format" => style_format = split_format(value),
+                "Style" => raw_styles.push(split_fields(&style_format, value)),
+                _ => {}
+            },
+            Section::Events => match key.trim() {
+                "Format" => event_format = split_format(value),
+                "Dialogue" => raw_events.push(split_fields(&event_format, value)),
+                _ => {}
+            },
+            Section::Other => {}
+        }
+    }
+
+    if event_format.is_empty() {
+        return Err(JsValue::from_str("Invalid ASS file: missing [Events] format line"));
+    }
+
+    // A missing PlayRes falls back to the other axis at 4:3, as renderers do.
+    let (res_x, res_y) = match (play_res_x, play_res_y) {
+        (Some(x), Some(y)) => (x, y),
+        (Some(x), None) => (x, x * 3.0 / 4.0),
+        (None, Some(y)) => (y * 4.0 / 3.0, y),
+        (None, None) => (PLAY_RES_X, PLAY_RES_Y),
+    };
+    let scale = PLAY_RES_Y / res_y;
+
+    let mut styles = HashMap::new();
+    for fields in &raw_styles {
+        let name = fields.get("name").cloned().unwrap_or_default();
+        styles.insert(name, style_from_fields(fields, base, scale, legacy_alignment));
+    }
+
+    let mut events = Vec::new();
+    for fields in &raw_events {
+        let start_ms = parse_time(fields.get("start").map(String::as_str).unwrap_or(""))?;
+        let end_ms = parse_time(fields.get("end").map(String::as_str).unwrap_or(""))?;
+
+        let style_name = fields.get("style").map(|s| s.trim_start_matches('*')).unwrap_or("Default");
+        let resolved = styles
+            .get(style_name)
+            .or_else(|| styles.get("Default"));
+        let mut style = resolved.map(|s| s.style.clone()).unwrap_or_else(|| base.clone());
+        let mut alignment = resolved.map(|s| s.alignment).unwrap_or(2);
+        let mut margin_v = resolved.map(|s| s.margin_v).unwrap_or(0.0);
+
+        // A non-zero event margin overrides the style margin.
+        if let Some(event_margin) = fields.get("marginv").and_then(|m| m.parse::<f32>().ok())
+            && event_margin > 0.0
+        {
+            margin_v = event_margin * scale;
+        }
+
+        let mut pos = None;
+        let raw_text = fields.get("text").cloned().unwrap_or_default();
+        let text = apply_overrides(&raw_text, &mut style, &mut alignment, &mut pos, scale);
+
+        style.alignment = text_align(alignment);
+        style.position = match pos {
+            Some((x, y)) => Position::Custom(
+                (x * 100.0 / res_x).round() as i32,
+                (y * 100.0 / res_y).round() as i32,
+            ),
+            None => position_for(alignment, margin_v),
+        };
+
+        let speaker = fields.get("name").filter(|n| !n.is_empty()).cloned();
+
+        events.push(AssEvent { start_ms, end_ms, text, speaker, style });
+    }
+
+    Ok(events)
+}
+
+fn split_format(value: &str) -> Vec<String> {
+    value.split(',').map(|f| f.trim().to_ascii_lowercase()).collect()
+}
+
+// The last field (Text) may itself contain commas, so only split as many times as the format has fields.
+fn split_fields(format: &[String], value: &str) -> HashMap<String, String> {
+    format
+        .iter()
+        .cloned()
+        .zip(value.splitn(format.len().max(1), ',').map(|v| v.trim().to_string()))
+        .collect()
+}
+
+// ASS timestamps are H:MM:SS.cc (centiseconds).
+fn parse_time(value: &str) -> Result<i32, JsValue> {
+    let invalid = || JsValue::from_str(&format!("Invalid ASS timestamp: {}", value));
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() != 3 {
+        return Err(invalid());
+    }
+    let hours: i32 = parts[0].parse().map_err(|_| invalid())?;
+    let minutes: i32 = parts[1].parse().map_err(|_| invalid())?;
+    let seconds: f64 = parts[2].parse().map_err(|_| invalid())?;
+
+    Ok(hours * 3600000 + minutes * 60000 + (seconds * 1000.0).round() as i32)
+}
+
+fn style_from_fields(
+    fields: &HashMap<String, String>,
+    base: &CaptionStyle,
+    scale: f32,
+    legacy_alignment: bool,
+) -> AssStyle {
+    let get = |key: &str| fields.get(key).map(String::as_str).unwrap_or("");
+    let number = |key: &str| get(key).parse::<f32>().ok();
+    let flag = |key: &str| get(key).parse::<i32>().map(|v| v != 0).unwrap_or(false);
+
+    let mut style = base.clone();
+
+    if !get("fontname").is_empty() {
+        style.font_family = get("fontname").to_string();
+    }
+    if let Some(size) = number("fontsize") {
+        style.font_size = (size * scale).round().max(1.0) as u32;
+    }
+    if let Some(color) = parse_color(get("primarycolour")) {
+        style.color = color;
+    }
+    style.bold = flag("bold");
+    style.italic = flag("italic");
+    style.underline = flag("underline");
+    if let Some(spacing) = number("spacing") {
+        style.letter_spacing = spacing * scale;
+    }
+
+    // SSA calls the outline colour "TertiaryColour".
+    let outline_color = parse_color(get("outlinecolour")).or_else(|| parse_color(get("tertiarycolour")));
+    let back_color = parse_color(get("backcolour"));
+
+    // BorderStyle 3 draws an opaque box in the outline colour instead of an outline.
+    let opaque_box = get("borderstyle") == "3";
+
+    let outline = if opaque_box { 0.0 } else { number("outline").unwrap_or(0.0) * scale };
+    style.outline_width = outline.round().max(0.0) as u32;
+    style.outline_color = if style.outline_width > 0 { outline_color.clone() } else { None };
+
+    let shadow = (number("shadow").unwrap_or(0.0) * scale).round() as i32;
+    style.shadow_offset_x = shadow;
+    style.shadow_offset_y = shadow;
+    style.shadow_color = if shadow != 0 { back_color } else { None };
+
+    style.background = if opaque_box {
+        outline_color.unwrap_or_else(|| base.background.clone())
+    } else {
+        String::from("#00000000")
+    };
+
+    let raw_alignment = get("alignment").parse::<u8>().unwrap_or(2);
+    let alignment = if legacy_alignment { from_legacy_alignment(raw_alignment) } else { raw_alignment };
+
+    AssStyle {
+        style,
+        alignment: if (1..=9).contains(&alignment) { alignment } else { 2 },
+        margin_v: number("marginv").unwrap_or(0.0) * scale,
+    }
+}
+
+// Strips `{...}` override blocks from the text, applying the tags they contain.
+fn apply_overrides(
+    raw: &str,
+    style: &mut CaptionStyle,
+    alignment: &mut u8,
+    pos: &mut Option<(f32, f32)>,
+    scale: f32,
+) -> String {
+    let mut text = String::with_capacity(raw.len());
+    let mut drawing = false;
+    let mut rest = raw;
+
+    loop {
+        let (plain, block) = match rest.find('{') {
+            Some(open) => match rest[open..].find('}') {
+                Some(close) => (&rest[..open], Some((&rest[open + 1..open + close], open + close + 1))),
+                None => (rest, None),
+            },
+            None => (rest, None),
+        };
+
+        if !drawing {
+            text.push_str(plain);
+        }
+
+        let Some((tags, next)) = block else {
+            break;
+        };
+        for tag in tags.split('\\').map(str::trim).filter(|t| !t.is_empty()) {
+            apply_tag(tag, style, alignment, pos, &mut drawing, scale);
+        }
+        rest = &rest[next..];
+    }
+
+    text.replace("\\N", "\n")
+        .replace("\\n", " ")
+        .replace("\\h", "\u{a0}")
+        .trim()
+        .to_string()
+}
+
+fn apply_tag(
+    tag: &str,
+    style: &mut CaptionStyle,
+    alignment: &mut u8,
+    pos: &mut Option<(f32, f32)>,
+    drawing: &mut bool,
+    scale: f32,
+) {
+    // Parenthesised arguments, e.g. "pos(320,240)" -> [320, 240].
+    let args = |name: &str| -> Vec<f32> {
+        tag[name.len()..]
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split(',')
+            .filter_map(|a| a.trim().parse().ok())
+            .collect()
+    };
+    let number = |name: &str| tag[name.len()..].trim().parse::<f32>().ok();
+
+    if tag.starts_with("pos(") {
+        if let [x, y, ..] = args("pos")[..] {
+            *pos = Some((x, y));
+        }
+    } else if tag.starts_with("fade(") || tag.starts_with("fad(") {
+        let values = if tag.starts_with("fade(") { args("fade") } else { args("fad") };
+        // \fade(a1,a2,a3,t1,t2,t3,t4): the fade-in runs from t1 to t2.
+        let fade_in_ms = match values[..] {
+            [fade_in, _] => fade_in,
+            [_, _, _, t1, t2, _, _] => t2 - t1,
+            _ => return,
+        };
+        style.animation_type = Some(String::from("fade"));
+        style.animation_duration = fade_in_ms.max(0.0) / 1000.0;
+    } else if let Some(value) = tag.strip_prefix("an") {
+        if let Ok(an) = value.parse::<u8>()
+            && (1..=9).contains(&an)
+        {
+            *alignment = an;
+        }
+    } else if let Some(Ok(a)) = tag.strip_prefix('a').map(str::parse::<u8>) {
+        *alignment = from_legacy_alignment(a);
+    } else if tag.starts_with("bord") {
+        if let Some(width) = number("bord") {
+            style.outline_width = (width * scale).round().max(0.0) as u32;
+        }
+    } else if tag.starts_with("shad") {
+        if let Some(offset) = number("shad") {
+            style.shadow_offset_x = (offset * scale).round() as i32;
+            style.shadow_offset_y = style.shadow_offset_x;
+        }
+    } else if let Some(name) = tag.strip_prefix("fn") {
+        if !name.is_empty() {
+            style.font_family = name.to_string();
+        }
+    } else if tag.starts_with("fs") {
+        if let Some(size) = number("fs") {
+            style.font_size = (size * scale).round().max(1.0) as u32;
+        }
+    } else if let Some(color) = tag.strip_prefix("1c").or_else(|| tag.strip_prefix("c")) {
+        if let Some(color) = parse_color(color) {
+            style.color = color;
+        }
+    } else if let Some(color) = tag.strip_prefix("3c") {
+        style.outline_color = parse_color(color);
+    } else if let Some(color) = tag.strip_prefix("4c") {
+        style.shadow_color = parse_color(color);
+    } else if let Some(value) = tag.strip_prefix("p") {
+        if let Ok(level) = value.parse::<i32>() {
+            *drawing = level > 0;
+        }
+    } else if let Some(value) = tag.strip_prefix("b") {
+        // \b accepts 0/1 or a font weight such as 700.
+        if let Ok(weight) = value.parse::<i32>() {
+            style.bold = weight == 1 || weight >= 600;
+        }
+    } else if let Some(Ok(value)) = tag.strip_prefix('i').map(str::parse::<i32>) {
+        style.italic = value == 1;
+    } else if let Some(Ok(value)) = tag.strip_prefix('u').map(str::parse::<i32>) {
+        style.underline = value == 1;
+    }
+}
+
+// Converts an ASS colour (&HAABBGGRR, &HBBGGRR& or a decimal BGR value) to CSS "#RRGGBB[AA]".
+pub fn parse_color(value: &str) -> Option<String> {
+    let value = value.trim().trim_end_matches('&');
+    let raw = if let Some(hex) = value.strip_prefix("&H").or_else(|| value.strip_prefix("&h")) {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if !value.is_empty() {
+        value.parse::<i64>().ok()? as u32
+    } else {
+        return None;
+    };
+
+    let alpha = 255 - ((raw >> 24) & 0xFF) as u8; // ASS alpha is inverted: 00 is opaque
+    let blue = (raw >> 16) & 0xFF;
+    let green = (raw >> 8) & 0xFF;
+    let red = raw & 0xFF;
+
+    Some(if alpha == 255 {
+        format!("#{:02X}{:02X}{:02X}", red, green, blue)
+    } else {
+        format!("#{:02X}{:02X}{:02X}{:02X}", red, green, blue, alpha)
+    })
+}
+
+// SSA alignment: 1-3 bottom, +4 top, +8 middle.
+fn from_legacy_alignment(value: u8) -> u8 {
+    match value {
+        1..=3 => value,
+        5..=7 => value + 2,
+        9..=11 => value - 5,
+        _ => 2,
+    }
+}
+
+fn text_align(alignment: u8) -> TextAlign {
+    match alignment % 3 {
+        1 => TextAlign::Left,
+        0 => TextAlign::Right,
+        _ => TextAlign::Center,
+    }
+}
+
+// Maps numpad alignment and vertical margin (in reference pixels) onto a Position.
+fn position_for(alignment: u8, margin_v: f32) -> Position {
+    let margin_percent = margin_v * 100.0 / PLAY_RES_Y;
+    match alignment {
+        7..=9 if margin_percent <= 10.0 => Position::Top,
+        7..=9 => Position::Custom(50, margin_percent.round() as i32),
+        4..=6 => Position::Middle,
+        _ if margin_percent <= 10.0 => Position::Bottom,
+        _ => Position::Custom(50, (100.0 - margin_percent).round() as i32),
+    }
+}
+
+// Writes a complete ASS script with one named style per distinct CaptionStyle.
+pub fn write(captions: &[Caption]) -> String {
+    let mut styles: Vec<&CaptionStyle> = Vec::new();
+    for caption in captions {
+        if !styles.contains(&&caption.style) {
+            styles.push(&caption.style);
+        }
+    }
+
+    let mut output = String::from("[Script Info]\n");
+    output.push_str("; Script generated by CaptionFlow\n");
+    output.push_str("ScriptType: v4.00+\n");
+    output.push_str(&format!("PlayResX: {}\n", PLAY_RES_X));
+    output.push_str(&format!("PlayResY: {}\n", PLAY_RES_Y));
+    output.push_str("WrapStyle: 0\n");
+    output.push_str("ScaledBorderAndShadow: yes\n\n");
+
+    output.push_str("[V4+ Styles]\n");
+    output.push_str(&format!("Format: {}\n", STYLE_FORMAT));
+    for (i, style) in styles.iter().enumerate() {
+        output.push_str(&format_style(&style_name(i), style));
+    }
+
+    output.push_str("\n[Events]\n");
+    output.push_str(&format!("Format: {}\n", EVENT_FORMAT));
+    for caption in captions {
+        let index = styles.iter().position(|s| **s == caption.style).unwrap_or(0);
+        output.push_str(&format!(
+            "Dialogue: 0,{},{},{},{},0,0,0,,{}{}\n",
+            format_time(caption.start_ms),
+            format_time(caption.end_ms),
+            style_name(index),
+            caption.speaker.as_deref().unwrap_or("").replace(',', ";"),
+            override_tags(&caption.style),
+            karaoke_text(caption).unwrap_or_else(|| caption.text.replace('\n', "\\N")),
+        ));
+    }
+
+    output
+}
+
+fn style_name(index: usize) -> String {
+    if index == 0 { String::from("Default") } else { format!("Style{}", index) }
+}
+
+fn format_style(name: &str, style: &CaptionStyle) -> String {
+    let flag = |on: bool| if on { -1 } else { 0 };
+
+    // A visible background becomes an opaque box, which ASS draws in the outline colour.
+    let opaque_box = css_alpha(&style.background) > 0;
+    let outline_color = if opaque_box {
+        style.background.as_str()
+    } else {
+        style.outline_color.as_deref().unwrap_or("#000000")
+    };
+    let back_color = style.shadow_color.as_deref().unwrap_or("#00000080");
+    let shadow = style.shadow_offset_x.abs().max(style.shadow_offset_y.abs());
+
+    // A narrower caption box is expressed through equal left and right margins.
+    let side_margin = match style.width_percent {
+        Some(width) if width > 0.0 && width < 100.0 => ((100.0 - width) / 200.0 * PLAY_RES_X).round() as u32,
+        _ => MARGIN,
+    };
+    let margin_v = match style.position {
+        Position::Bottom | Position::Top => MARGIN,
+        _ => 0,
+    };
+
+    format!(
+        "Style: {},{},{},{},&H000000FF,{},{},{},{},{},0,100,100,{},0,{},{},{},{},{},{},{},1\n",
+        name,
+        style.font_family.replace(',', " "),
+        style.font_size,
+        format_color(&style.color),
+        format_color(outline_color),
+        format_color(back_color),
+        flag(style.bold),
+        flag(style.italic),
+        flag(style.underline),
+        style.letter_spacing,
+        if opaque_box { 3 } else { 1 },
+        style.outline_width,
+        shadow,
+        alignment_for(style),
+        side_margin,
+        side_margin,
+        margin_v,
+    )
+}
+
+// Per-line tags for things a Style line cannot express.
+fn override_tags(style: &CaptionStyle) -> String {
+    let mut tags = String::new();
+
+    if let Position::Custom(x, y) = style.position {
+        tags.push_str(&format!(
+            "\\pos({},{})",
+            (x as f32 * PLAY_RES_X / 100.0).round(),
+            (y as f32 * PLAY_RES_Y / 100.0).round()
+        ));
+    }
+    if style.shadow_offset_x != style.shadow_offset_y {
+        tags.push_str(&format!("\\xshad{}\\yshad{}", style.shadow_offset_x, style.shadow_offset_y));
+    }
+    if style.animation_type.as_deref() == Some("fade") {
+        let fade_ms = (style.animation_duration * 1000.0).round() as i32;
+        tags.push_str(&format!("\\fad({},{})", fade_ms, fade_ms));
+    }
+
+    if tags.is_empty() { tags } else { format!("{{{}}}", tags) }
+}
+
+// With the "karaoke" animation, each word gets a \kf sweep timed from its word
+// timing, and pauses between words become plain \k delays.
+fn karaoke_text(caption: &Caption) -> Option<String> {
+    if caption.style.animation_type.as_deref() != Some("karaoke")
+        || !words::matches_text(&caption.words, &caption.text)
+    {
+        return None;
+    }
+
+    let centiseconds = |ms: i32| (ms.max(0) + 5) / 10;
+    let mut output = String::new();
+    let mut words = caption.words.iter();
+    let mut cursor = caption.start_ms;
+    let mut in_word = false;
+
+    for c in caption.text.chars() {
+        if c.is_whitespace() {
+            in_word = false;
+            output.push_str(if c == '\n' { "\\N" } else { " " });
+            continue;
+        }
+        if !in_word
+            && let Some(word) = words.next()
+        {
+            let gap = centiseconds(word.start_ms - cursor);
+            if gap > 0 {
+                output.push_str(&format!("{{\\k{}}}", gap));
+            }
+            let start = word.start_ms.max(cursor);
+            output.push_str(&format!("{{\\kf{}}}", centiseconds(word.end_ms - start)));
+            cursor = word.end_ms.max(start);
+        }
+        in_word = true;
+        output.push(c);
+    }
+
+    Some(output)
+}
+
+// Numpad alignment: rows come from the position, columns from the text alignment.
+fn alignment_for(style: &CaptionStyle) -> u8 {
+    let row = match style.position {
+        Position::Bottom => 0,
+        Position::Middle | Position::Custom(..) => 3,
+        Position::Top => 6,
+    };
+    let column = match style.alignment {
+        TextAlign::Left => 1,
+        TextAlign::Center => 2,
+        TextAlign::Right => 3,
+    };
+    row + column
+}
+
+fn format_time(ms: i32) -> String {
+    let ms = ms.max(0);
+    let centiseconds = (ms + 5) / 10;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centiseconds / 360000,
+        (centiseconds % 360000) / 6000,
+        (centiseconds % 6000) / 100,
+        centiseconds % 100
+    )
+}
+
+// Splits a CSS "#RGB", "#RRGGBB" or "#RRGGBBAA" colour into its channels.
+fn css_channels(css: &str) -> Option<(u8, u8, u8, u8)> {
+    let hex = css.trim().strip_prefix('#')?;
+    let expanded: String = if hex.len() == 3 || hex.len() == 4 {
+        hex.chars().flat_map(|c| [c, c]).collect()
+    } else {
+        hex.to_string()
+    };
+    let channel = |i: usize| u8::from_str_radix(expanded.get(i..i + 2)?, 16).ok();
+    match expanded.len() {
+        6 => Some((channel(0)?, channel(2)?, channel(4)?, 255)),
+        8 => Some((channel(0)?, channel(2)?, channel(4)?, channel(6)?)),
+        _ => None,
+    }
+}
+
+fn css_alpha(css: &str) -> u8 {
+    css_channels(css).map(|(_, _, _, a)| a).unwrap_or(0)
+}
+
+// Converts a CSS colour to ASS "&HAABBGGRR", inverting the alpha channel.
+pub fn format_color(css: &str) -> String {
+    let (red, green, blue, alpha) = css_channels(css).unwrap_or((255, 255, 255, 255));
+    format!("&H{:02X}{:02X}{:02X}{:02X}", 255 - alpha, blue, green, red)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captioneditor::CaptionEditor;
+    use crate::structs::{Word, caption};
+
+    const SCRIPT: &str = r"[Script Info]
+; Authored at 720p, so sizes scale by 288/720
+PlayResX: 1280
+PlayResY: 720
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,Roboto,60,&H0000FFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,5,0,2,20,20,30,1
+Style: Sign,Arial,40,&H00FFFFFF,&H000000FF,&H40302010,&H00000000,-1,0,0,0,100,100,0,0,3,2,0,8,20,20,30,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: 0,0:00:01.00,0:00:03.50,Default,Anna,0,0,0,,Hello, {\i1}world\Nsecond line
+Comment: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,not shown
+Dialogue: 0,0:00:04.00,0:01:05.25,Sign,,0,0,0,,{\pos(640,360)\fad(200,0)}EXIT
+Dialogue: 0,0:00:06.00,0:00:07.00,Missing,,0,0,0,,{\an7\b700\fs80}Top left
+";
+
+    #[test]
+    fn parses_styles_and_override_tags() {
+        let events = parse(SCRIPT, &CaptionEditor::default_style()).unwrap();
+        assert_eq!(events.len(), 3);
+
+        let first = &events[0];
+        assert_eq!((first.start_ms, first.end_ms), (1000, 3500));
+        assert_eq!(first.text, "Hello, world\nsecond line");
+        assert_eq!(first.speaker.as_deref(), Some("Anna"));
+        assert_eq!(first.style.font_family, "Roboto");
+        assert_eq!(first.style.font_size, 24);
+        assert_eq!(first.style.color, "#FFFF00");
+        assert_eq!((first.style.outline_width, first.style.outline_color.as_deref()), (2, Some("#000000")));
+        assert_eq!(first.style.background, "#00000000");
+        assert!(first.style.italic);
+        assert_eq!(first.style.position, Position::Bottom);
+
+        // BorderStyle 3 draws a box in the outline colour.
+        let sign = &events[1];
+        assert_eq!((sign.start_ms, sign.end_ms), (4000, 65_250));
+        assert_eq!(sign.text, "EXIT");
+        assert_eq!(sign.speaker, None);
+        assert_eq!(sign.style.background, "#102030BF");
+        assert_eq!(sign.style.outline_width, 0);
+        assert!(sign.style.bold);
+        assert_eq!(sign.style.position, Position::Custom(50, 50));
+        assert_eq!(sign.style.animation_type.as_deref(), Some("fade"));
+        assert_eq!(sign.style.animation_duration, 0.2);
+
+        // An unknown style falls back to Default.
+        let top = &events[2];
+        assert_eq!(top.style.font_family, "Roboto");
+        assert_eq!(top.style.font_size, 32);
+        assert!(top.style.bold);
+        assert_eq!((top.style.position.clone(), top.style.alignment.clone()), (Position::Top, TextAlign::Left));
+    }
+
+    #[test]
+    fn reads_legacy_ssa_alignment_and_colours() {
+        let script = "[Script Info]\nScriptType: v4.00\n\n[V4 Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, TertiaryColour, BackColour, Bold, Italic, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, AlphaLevel, Encoding\n\
+            Style: Default,Arial,20,16777215,65535,255,0,0,0,1,2,0,6,10,10,10,0,0\n\n\
+            [Events]\nFormat: Marked, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: Marked=0,0:00:00.50,0:00:01.00,Default,,0000,0000,0000,,Hi\n";
+        let events = parse(script, &CaptionEditor::default_style()).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].style.position, Position::Top);
+        assert_eq!(events[0].style.alignment, TextAlign::Center);
+        assert_eq!(events[0].style.outline_color.as_deref(), Some("#FF0000"));
+        assert_eq!(events[0].text, "Hi");
+    }
+
+    #[test]
+    fn written_styles_parse_back_unchanged() {
+        let plain = caption("1", 1000, 2500, "Default style\non two lines");
+
+        let mut outlined = caption("2", 3000, 4000, "Bold, top, left");
+        outlined.speaker = Some(String::from("Anna"));
+        let style = &mut outlined.style;
+        (style.bold, style.italic, style.underline) = (true, true, true);
+        style.position = Position::Top;
+        style.alignment = TextAlign::Left;
+        style.font_family = String::from("Roboto");
+        style.font_size = 24;
+        style.color = String::from("#FFD400");
+        style.background = String::from("#00000000");
+        style.outline_width = 2;
+        style.outline_color = Some(String::from("#101010"));
+        style.shadow_offset_x = 1;
+        style.shadow_offset_y = 1;
+        style.shadow_color = Some(String::from("#00000080"));
+
+        let mut placed = caption("3", 5000, 6500, "Placed and fading");
+        placed.style.position = Position::Custom(25, 40);
+        placed.style.animation_type = Some(String::from("fade"));
+        placed.style.animation_duration = 0.25;
+
+        let captions = [plain, outlined, placed];
+        let parsed = parse(&write(&captions), &CaptionEditor::default_style()).unwrap();
+        assert_eq!(parsed.len(), 3);
+        for (event, caption) in parsed.iter().zip(&captions) {
+            assert_eq!((event.start_ms, event.end_ms), (caption.start_ms, caption.end_ms));
+            assert_eq!(event.text, caption.text);
+            assert_eq!(event.speaker, caption.speaker);
+            assert_eq!(event.style, caption.style);
+        }
+    }
+
+    #[test]
+    fn writes_karaoke_timings_from_words() {
+        let word =
+            |text: &str, start_ms: i32, end_ms: i32| Word { text: text.into(), start_ms, end_ms, confidence: 1.0 };
+        let mut sung = caption("1", 500, 4000, "la la\nlo");
+        sung.style.animation_type = Some(String::from("karaoke"));
+        sung.words = vec![word("la", 1000, 1500), word("la", 2000, 3000), word("lo", 3000, 4000)];
+
+        // Words that no longer match the text fall back to plain text.
+        let mut edited = sung.clone();
+        edited.text = String::from("la la\nla");
+
+        let script = write(&[sung, edited]);
+        let texts: Vec<&str> = script
+            .lines()
+            .filter_map(|line| line.strip_prefix("Dialogue: "))
+            .map(|line| line.splitn(10, ',').last().unwrap())
+            .collect();
+        assert_eq!(texts, [r"{\k50}{\kf50}la {\k50}{\kf100}la\N{\kf100}lo", r"la la\Nla"]);
+    }
+}
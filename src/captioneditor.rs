@@ -1,7 +1,25 @@
-use crate::structs::{Caption, CaptionStyle, Position};
+use crate::ass;
+use crate::detect;
+use crate::ebu_stl;
+use crate::edl;
+use crate::encoding;
+use crate::fcpxml;
+use crate::history::{History, Touched};
+use crate::opus;
+use crate::qc;
+use crate::reflow;
+use crate::scc;
+use crate::segment;
+use crate::srt;
+use crate::structs::{Caption, CaptionStyle, Position, TextAlign, Word};
+use crate::timecode::FrameRate;
+use crate::ttml;
+use crate::whisper;
+use crate::words;
+use crate::youtube;
 use log::{Level, info};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::HashSet;
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::prelude::*;
 use web_sys::{AudioContext, HtmlVideoElement, console};
@@ -9,12 +27,16 @@ use web_sys::{AudioContext, HtmlVideoElement, console};
 #[wasm_bindgen]
 pub struct CaptionEditor {
     captions: Vec<Caption>,
-    selected_indices: Vec<usize>,
+    selected_ids: HashSet<String>,
     caption_id_nonce: u32,
-    history: VecDeque<Vec<Caption>>,
-    history_index: usize,
+    history: History,
     waveform_data: Vec<f32>,
+    silence_threshold: f32,
     video_duration_ms: i32,
+    frame_rate: FrameRate,
+    qc_profile: qc::Profile,
+    shot_changes: Vec<i32>,
+    measure_context: Option<web_sys::CanvasRenderingContext2d>,
     playback_rate: f32,
     auto_save_enabled: bool,
 }
@@ -23,8 +45,7 @@ pub struct CaptionEditor {
 impl CaptionEditor {
     #[wasm_bindgen]
     pub fn create_caption(&mut self, start_ms: i32) -> String {
-        let id = format!("caption_{}", self.caption_id_nonce);
-        self.caption_id_nonce += 1;
+        let id = self.next_caption_id();
 
         let new_caption = Caption {
             id: id.clone(),
@@ -34,12 +55,14 @@ impl CaptionEditor {
             confidence: 1.0,  // A user-created caption has 100% confidence
             speaker: None,
             style: Self::default_style(), // Use the visible default style
+            words: Vec::new(),
+            track: None,
         };
 
-        self.captions.push(new_caption);
+        let index = self.captions.partition_point(|c| c.start_ms <= start_ms);
+        self.captions.insert(index, new_caption);
 
-        // Use the safe, correct history pattern
-        self.record_history_snapshot();
+        self.history.record("Add caption", &self.captions, &[Touched::Inserted(index)]);
 
         info!("Live caption created with ID: '{}'", id);
         id // Return the unique ID to JavaScript
@@ -50,9 +73,9 @@ impl CaptionEditor {
     pub fn update_caption_text(&mut self, id: &str, new_text: &str) {
         info!("Updating text for caption '{}' to: \"{}\"", id, new_text); // <-- LOG
 
-        if let Some(caption) = self.captions.iter_mut().find(|c| c.id == id) {
-            caption.text = new_text.to_string();
-            self.record_history_snapshot(); // Save after mutation
+        if let Some(index) = self.captions.iter().position(|c| c.id == id) {
+            words::set_text(&mut self.captions[index], new_text.to_string());
+            self.history.record("Edit text", &self.captions, &[Touched::Changed(index, index)]);
         } else {
             log::warn!(
                 "update_caption_text failed: could not find caption with ID '{}'",
@@ -73,10 +96,13 @@ impl CaptionEditor {
             end_ms
         ); // <-- LOG
 
-        if let Some(caption) = self.captions.iter_mut().find(|c| c.id == id) {
+        if let Some(index) = self.captions.iter().position(|c| c.id == id) {
+            let caption = &mut self.captions[index];
+            words::retime(&mut caption.words, (caption.start_ms, caption.end_ms), (start_ms, end_ms));
             caption.start_ms = start_ms;
             caption.end_ms = end_ms;
-            self.record_history_snapshot(); // Save after mutation
+            let touched = self.reposition(&[index]);
+            self.history.record("Change timing", &self.captions, &touched);
         } else {
             log::warn!(
                 "update_caption_timing failed: could not find caption with ID '{}'",
@@ -88,47 +114,103 @@ impl CaptionEditor {
     pub fn new() -> Self {
         console::log_1(&"Initializing Caption Editor in Rust/WASM".into());
 
-        // Create an initial empty state and add it to history
-        let initial_captions = Vec::new();
-        let mut history = VecDeque::with_capacity(100);
-        history.push_back(initial_captions.clone());
-
         CaptionEditor {
-            captions: initial_captions,
-            selected_indices: Vec::new(),
-            history,
+            captions: Vec::new(),
+            selected_ids: HashSet::new(),
+            history: History::new(&[]),
             caption_id_nonce: 0,
-            history_index: 0,
             waveform_data: Vec::new(),
+            silence_threshold: 0.0,
             video_duration_ms: 0,
+            frame_rate: FrameRate::default(),
+            qc_profile: qc::netflix(),
+            shot_changes: Vec::new(),
+            measure_context: None,
             playback_rate: 1.0,
             auto_save_enabled: true,
         }
     }
+    // Frame rate used by frame-based exports (FCPXML, EDL). Drop-frame applies to 29.97/59.94 only.
+    #[wasm_bindgen]
+    pub fn set_frame_rate(&mut self, fps: f64, drop_frame: bool) -> Result<(), JsValue> {
+        self.frame_rate = FrameRate::from_fps(fps, drop_frame)?;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn set_video_duration(&mut self, duration_ms: i32) {
+        self.video_duration_ms = duration_ms.max(0);
+    }
+
+    // Loads an amplitude envelope spanning the whole video, e.g. from
+    // WaveformProcessor::get_waveform_data, for snapping and silence detection.
+    // Samples below a tenth of the loudest one count as silence.
+    #[wasm_bindgen]
+    pub fn load_waveform(&mut self, amplitudes: Vec<f32>, duration_ms: i32) {
+        self.waveform_data = amplitudes.into_iter().map(f32::abs).collect();
+        self.silence_threshold = self.waveform_data.iter().fold(0.0f32, |max, &a| max.max(a)) * 0.1;
+        self.set_video_duration(duration_ms);
+    }
+
+    // Moves a caption's in point to the nearest speech onset and its out point
+    // to the nearest speech offset, each within `window_ms`. Returns whether it moved.
+    #[wasm_bindgen]
+    pub fn snap_caption_to_audio(&mut self, id: &str, window_ms: i32) -> Result<bool, JsValue> {
+        if self.waveform_data.is_empty() || self.video_duration_ms <= 0 {
+            return Err(JsValue::from_str("Load a waveform and video duration first"));
+        }
+        let order = qc::timeline_order(&self.captions);
+        let position = order
+            .iter()
+            .position(|&i| self.captions[i].id == id)
+            .ok_or_else(|| JsValue::from_str(&format!("Caption not found: {}", id)))?;
+
+        let snapped = self.snap_to_audio(&order, position, window_ms);
+        let touched = if snapped { self.reposition(&[order[position]]) } else { Vec::new() };
+        self.history.record("Snap to audio", &self.captions, &touched);
+        Ok(snapped)
+    }
+
+    // Snaps every caption to the audio as one undo step. Returns how many moved.
+    #[wasm_bindgen]
+    pub fn snap_all_to_audio(&mut self, window_ms: i32) -> Result<u32, JsValue> {
+        if self.waveform_data.is_empty() || self.video_duration_ms <= 0 {
+            return Err(JsValue::from_str("Load a waveform and video duration first"));
+        }
+        let order = qc::timeline_order(&self.captions);
+        let moved: Vec<usize> =
+            (0..order.len()).filter(|&position| self.snap_to_audio(&order, position, window_ms)).map(|p| order[p]).collect();
+
+        let touched = self.reposition(&moved);
+        self.history.record("Snap all to audio", &self.captions, &touched);
+        Ok(moved.len() as u32)
+    }
+
     #[wasm_bindgen]
     pub fn add_caption(&mut self, start_ms: i32, end_ms: i32, text: &str) {
         let new_caption = Caption {
-            id: format!("caption_{}", self.caption_id_nonce),
+            id: self.next_caption_id(),
             start_ms,
             end_ms,
             text: text.to_string(),
             speaker: None,
             confidence: 1.0,
             style: Self::default_style(),
+            words: Vec::new(),
+            track: None,
         };
-        self.caption_id_nonce += 1;
-        self.captions.push(new_caption);
-        self.captions.sort_by_key(|c| c.start_ms);
-        self.record_history_snapshot();
+        let index = self.captions.partition_point(|c| c.start_ms <= start_ms);
+        self.captions.insert(index, new_caption);
+        self.history.record("Add caption", &self.captions, &[Touched::Inserted(index)]);
     }
 
     #[wasm_bindgen]
     pub fn update_caption_style(&mut self, id: &str, style: JsValue) -> Result<(), JsValue> {
         
         let new_style: CaptionStyle = serde_wasm_bindgen::from_value(style)?;
-        if let Some(caption) = self.captions.iter_mut().find(|c| c.id == id) {
-            caption.style = new_style;
-            self.record_history_snapshot();
+        if let Some(index) = self.captions.iter().position(|c| c.id == id) {
+            self.captions[index].style = new_style;
+            self.history.record("Change style", &self.captions, &[Touched::Changed(index, index)]);
         }
         Ok(())
     }
@@ -139,56 +221,157 @@ impl CaptionEditor {
         for caption in &mut self.captions {
             caption.style = new_style.clone();
         }
-        self.record_history_snapshot();
+        self.history.record("Change style of all captions", &self.captions, &self.all_changed());
         Ok(())
     }
 
-    fn record_history_snapshot(&mut self) {
-        if self.history_index < self.history.len() - 1 {
-            self.history.truncate(self.history_index + 1);
-        }
-
-        self.history.push_back(self.captions.clone());
-        self.history_index += 1;
-
-        if self.history.len() > 100 {
-            self.history.pop_front();
-            self.history_index = self.history.len() - 1;
-        }
-    }
     #[wasm_bindgen]
     pub fn delete_captions(&mut self, ids: JsValue) -> Result<(), JsValue> {
-        let ids_to_delete: Vec<String> = serde_wasm_bindgen::from_value(ids)?;
-        self.save_history();
+        let ids_to_delete: HashSet<String> = serde_wasm_bindgen::from_value(ids)?;
 
-        self.captions.retain(|c| !ids_to_delete.contains(&c.id));
+        let touched = self.remove_where(|c| ids_to_delete.contains(&c.id));
+        self.history.record("Delete captions", &self.captions, &touched);
 
         Ok(())
     }
     #[wasm_bindgen]
     pub fn import_captions(&mut self, format: &str, content: &str) -> Result<(), JsValue> {
-        self.save_history();
+        let imported = Self::parse_format(format, content)?;
+        let touched = self.replace_captions(imported);
+        self.history.record(&format!("Import {}", format), &self.captions, &touched);
 
-        match format {
-            "srt" => self.parse_srt(content)?,
-            "vtt" => self.parse_vtt(content)?,
-            "ass" => self.parse_ass(content)?,
-            "json" => self.parse_json(content)?,
-            "opus" => self.parse_opus_export(content)?,
-            _ => return Err(JsValue::from_str("Unsupported format")),
+        Ok(())
+    }
+
+    // Imports SRT, returning a JSON list of `{ line, message }` warnings for the
+    // blocks that were malformed. In strict mode the first malformed block fails the import.
+    #[wasm_bindgen]
+    pub fn import_srt(&mut self, content: &str, strict: bool) -> Result<String, JsValue> {
+        let (imported, warnings) = srt::parse(content, &Self::default_style(), strict)?;
+        let touched = self.replace_captions(imported);
+        self.history.record("Import srt", &self.captions, &touched);
+
+        Ok(serde_json::to_string(&warnings).unwrap_or_default())
+    }
+
+    // Imports whisper.cpp full JSON output (`-ojf`), keeping word timestamps and
+    // taking confidence from the token probabilities.
+    #[wasm_bindgen]
+    pub fn import_whisper_json(&mut self, content: &str) -> Result<(), JsValue> {
+        self.import_captions("whisper", content)
+    }
+
+    // Detects the format from the content and imports it, returning the format used.
+    #[wasm_bindgen]
+    pub fn import_captions_auto(&mut self, content: &str) -> Result<String, JsValue> {
+        let format = match detect::detect_format(content) {
+            Some("fcpxml") => return Err(JsValue::from_str("FCPXML files can be exported but not imported")),
+            Some(format) => format,
+            None => return Err(JsValue::from_str("Could not detect caption format")),
+        };
+
+        self.import_captions(format, content)?;
+        Ok(format.to_string())
+    }
+
+    // Imports a file from raw bytes, detecting its character encoding unless one is
+    // given. `format` may be "auto". Returns JSON with the format and encoding used.
+    #[wasm_bindgen]
+    pub fn import_captions_bytes(
+        &mut self,
+        format: &str,
+        content: &[u8],
+        encoding: Option<String>,
+    ) -> Result<String, JsValue> {
+        if matches!(format, "stl" | "ebu-stl") || (format == "auto" && ebu_stl::is_ebu_stl(content)) {
+            self.import_captions_binary("stl", content)?;
+            return Ok(serde_json::json!({ "format": "stl", "encoding": null }).to_string());
         }
 
-        self.analyze_reading_speed();
-        self.detect_conflicts();
+        let (text, encoding_name) = encoding::decode(content, encoding.as_deref())?;
+        let format = if format == "auto" {
+            self.import_captions_auto(&text)?
+        } else {
+            self.import_captions(format, &text)?;
+            format.to_string()
+        };
+
+        Ok(serde_json::json!({ "format": format, "encoding": encoding_name }).to_string())
+    }
+
+    #[wasm_bindgen]
+    pub fn import_captions_binary(&mut self, format: &str, content: &[u8]) -> Result<(), JsValue> {
+        let imported = match format {
+            "stl" | "ebu-stl" => ebu_stl::parse(content, self.frame_rate, &Self::default_style())?,
+            _ => return Err(JsValue::from_str("Unsupported format")),
+        };
+        let touched = self.replace_captions(imported);
+        self.history.record(&format!("Import {}", format), &self.captions, &touched);
+
         Ok(())
     }
 
+    // Imports into the current project instead of replacing it. Imported cues are
+    // shifted by `offset_ms` and placed on `track` (a track name or language code).
+    // Returns a JSON list of `{ imported_id, existing_id }` overlaps on the same track.
     #[wasm_bindgen]
-    pub fn split_caption(&mut self, caption_id: &str, split_time_ms: i32) -> Result<(), JsValue> {
-        self.save_history();
+    pub fn import_captions_merge(
+        &mut self,
+        format: &str,
+        content: &str,
+        offset_ms: i32,
+        track: Option<String>,
+    ) -> Result<String, JsValue> {
+        let format = if format == "auto" {
+            detect::detect_format(content).ok_or_else(|| JsValue::from_str("Could not detect caption format"))?
+        } else {
+            format
+        };
+        let mut imported = Self::parse_format(format, content)?;
+
+        for caption in &mut imported {
+            caption.start_ms = (caption.start_ms + offset_ms).max(0);
+            caption.end_ms = (caption.end_ms + offset_ms).max(caption.start_ms);
+            for word in &mut caption.words {
+                word.start_ms = (word.start_ms + offset_ms).max(0);
+                word.end_ms = (word.end_ms + offset_ms).max(word.start_ms);
+            }
+            if track.is_some() {
+                caption.track = track.clone();
+            }
+        }
+
+        let touched = self.add_imported(imported);
+        self.history.record(&format!("Import {} into project", format), &self.captions, &touched);
+
+        let imported_ids: HashSet<&str> = touched
+            .iter()
+            .filter_map(|t| match t {
+                Touched::Inserted(index) => Some(self.captions[*index].id.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        // Only overlaps between an imported and an existing caption are reported.
+        let conflicts: Vec<serde_json::Value> = qc::find_overlaps(&self.captions)
+            .into_iter()
+            .filter_map(|c| {
+                match (imported_ids.contains(c.first_id.as_str()), imported_ids.contains(c.second_id.as_str())) {
+                    (true, false) => Some((c.first_id, c.second_id)),
+                    (false, true) => Some((c.second_id, c.first_id)),
+                    _ => None,
+                }
+            })
+            .map(|(imported_id, existing_id)| serde_json::json!({ "imported_id": imported_id, "existing_id": existing_id }))
+            .collect();
 
+        Ok(serde_json::to_string(&conflicts).unwrap_or_default())
+    }
+
+    #[wasm_bindgen]
+    pub fn split_caption(&mut self, caption_id: &str, split_time_ms: i32) -> Result<(), JsValue> {
         if let Some(index) = self.captions.iter().position(|c| c.id == caption_id) {
-            let original = self.captions[index].clone();
+            let original = &self.captions[index];
 
             if split_time_ms <= original.start_ms || split_time_ms >= original.end_ms {
                 return Err(JsValue::from_str(
@@ -196,108 +379,395 @@ impl CaptionEditor {
                 ));
             }
 
-            let ratio = (split_time_ms - original.start_ms) as f32
-                / (original.end_ms - original.start_ms) as f32;
-            let words: Vec<&str> = original.text.split_whitespace().collect();
-            let split_index = (words.len() as f32 * ratio) as usize;
+            // With word timings the split falls between the words either side of
+            // the split time; otherwise it is estimated from the word count.
+            let (first_words, second_words) = words::split_at(&original.words, split_time_ms);
+            let tokens = words::token_spans(&original.text);
+            let split_index = if words::matches_text(&original.words, &original.text) {
+                first_words.len()
+            } else {
+                let ratio = (split_time_ms - original.start_ms) as f32
+                    / (original.end_ms - original.start_ms) as f32;
+                (tokens.len() as f32 * ratio) as usize
+            };
+            let offset = tokens.get(split_index).map_or(original.text.len(), |&(start, _)| start);
+
+            self.split_caption_parts(index, offset, split_time_ms, first_words, second_words);
+            let touched = [Touched::Changed(index, index), Touched::Inserted(index + 1)];
+            self.history.record("Split caption", &self.captions, &touched);
+        }
 
-            let first_text = words[..split_index].join(" ");
-            let second_text = words[split_index..].join(" ");
+        Ok(())
+    }
 
-            let mut first = original.clone();
-            first.end_ms = split_time_ms;
-            first.text = first_text;
+    // Splits a caption where the text cursor is, `char_offset` characters into
+    // its text. The split time comes from the word timings when they match the
+    // text, or else from the share of characters before the cursor. Line breaks
+    // and spacing within each half are kept. Returns the id of the new second half.
+    #[wasm_bindgen]
+    pub fn split_caption_at_text(&mut self, caption_id: &str, char_offset: usize) -> Result<String, JsValue> {
+        let index = self
+            .captions
+            .iter()
+            .position(|c| c.id == caption_id)
+            .ok_or_else(|| JsValue::from_str(&format!("Caption not found: {}", caption_id)))?;
+        let original = &self.captions[index];
+
+        let offset = original
+            .text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(original.text.len()))
+            .nth(char_offset)
+            .ok_or_else(|| JsValue::from_str("Split position is past the end of the caption text"))?;
+        let (head, tail) = original.text.split_at(offset);
+        if head.trim().is_empty() || tail.trim().is_empty() {
+            return Err(JsValue::from_str("Split position must leave text on both sides"));
+        }
+        if original.end_ms - original.start_ms < 2 {
+            return Err(JsValue::from_str("Caption is too short to split"));
+        }
+
+        let (first_words, second_words, split_ms) =
+            match words::split_at_offset(&original.words, &original.text, offset) {
+                Some(split) => split,
+                None => {
+                    let head_chars = head.trim().chars().count() as i64;
+                    let total_chars = head_chars + tail.trim().chars().count() as i64;
+                    let duration = (original.end_ms - original.start_ms) as i64;
+                    let split_ms = original.start_ms + (duration * head_chars / total_chars) as i32;
+                    let (first_words, second_words) = words::split_at(&original.words, split_ms);
+                    (first_words, second_words, split_ms)
+                }
+            };
+        let split_ms = split_ms.clamp(original.start_ms + 1, original.end_ms - 1);
+
+        let id = self.split_caption_parts(index, offset, split_ms, first_words, second_words);
+        let touched = [Touched::Changed(index, index), Touched::Inserted(index + 1)];
+        self.history.record("Split caption", &self.captions, &touched);
+        Ok(id)
+    }
 
-            let mut second = original.clone();
-            second.id = format!("{}_split", original.id);
-            second.start_ms = split_time_ms;
-            second.text = second_text;
+    // Selection is kept by caption id, so it survives re-sorting, inserts and
+    // deletes. Ids that no longer exist are ignored.
+    #[wasm_bindgen]
+    pub fn select(&mut self, ids: JsValue) -> Result<(), JsValue> {
+        let ids: Vec<String> = serde_wasm_bindgen::from_value(ids)?;
+        self.selected_ids.clear();
+        self.selected_ids.extend(ids);
+        Ok(())
+    }
 
-            self.captions[index] = first;
-            self.captions.insert(index + 1, second);
+    #[wasm_bindgen]
+    pub fn add_to_selection(&mut self, ids: JsValue) -> Result<(), JsValue> {
+        let ids: Vec<String> = serde_wasm_bindgen::from_value(ids)?;
+        self.selected_ids.extend(ids);
+        Ok(())
+    }
+
+    // Selects every caption that overlaps [start_ms, end_ms).
+    #[wasm_bindgen]
+    pub fn select_range(&mut self, start_ms: i32, end_ms: i32) {
+        self.selected_ids = self
+            .captions
+            .iter()
+            .filter(|c| c.start_ms < end_ms && c.end_ms > start_ms)
+            .map(|c| c.id.clone())
+            .collect();
+    }
+
+    #[wasm_bindgen]
+    pub fn select_all(&mut self) {
+        self.selected_ids = self.captions.iter().map(|c| c.id.clone()).collect();
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_selection(&mut self) {
+        self.selected_ids.clear();
+    }
+
+    // Returns the selected ids as a JSON array, in timeline order.
+    #[wasm_bindgen]
+    pub fn get_selection(&self) -> String {
+        let ids: Vec<&str> = self.selected_positions().into_iter().map(|i| self.captions[i].id.as_str()).collect();
+        serde_json::to_string(&ids).unwrap_or_default()
+    }
+
+    #[wasm_bindgen]
+    pub fn delete_selected(&mut self) {
+        if self.selected_positions().is_empty() {
+            return;
+        }
+        let selected = std::mem::take(&mut self.selected_ids);
+        let touched = self.remove_where(|c| selected.contains(&c.id));
+        self.history.record("Delete captions", &self.captions, &touched);
+    }
+
+    #[wasm_bindgen]
+    pub fn shift_selected(&mut self, shift_ms: i32) {
+        let positions = self.selected_positions();
+        if positions.is_empty() {
+            return;
+        }
+        for &i in &positions {
+            let caption = &mut self.captions[i];
+            let (start_ms, end_ms) = (caption.start_ms + shift_ms, caption.end_ms + shift_ms);
+            words::retime(&mut caption.words, (caption.start_ms, caption.end_ms), (start_ms, end_ms));
+            caption.start_ms = start_ms;
+            caption.end_ms = end_ms;
         }
+        let touched = self.reposition(&positions);
+        self.history.record(&format!("Shift selected {:+}ms", shift_ms), &self.captions, &touched);
+    }
 
+    #[wasm_bindgen]
+    pub fn update_selected_style(&mut self, style: JsValue) -> Result<(), JsValue> {
+        let new_style: CaptionStyle = serde_wasm_bindgen::from_value(style)?;
+        let positions = self.selected_positions();
+        if positions.is_empty() {
+            return Ok(());
+        }
+        for &i in &positions {
+            self.captions[i].style = new_style.clone();
+        }
+        let touched: Vec<Touched> = positions.into_iter().map(|i| Touched::Changed(i, i)).collect();
+        self.history.record("Change style of selected captions", &self.captions, &touched);
         Ok(())
     }
 
+    // Merges the selected captions into the earliest one, which stays selected.
     #[wasm_bindgen]
     pub fn merge_selected(&mut self) -> Result<(), JsValue> {
-        if self.selected_indices.len() < 2 {
+        let positions = self.selected_positions();
+        if positions.len() < 2 {
             return Err(JsValue::from_str("Select at least 2 captions to merge"));
         }
 
-        self.save_history();
-        self.selected_indices.sort();
-
-        let first_idx = self.selected_indices[0];
-        let last_idx = self.selected_indices[self.selected_indices.len() - 1];
-
-        let merged_text = self
-            .selected_indices
+        let merged_text = positions
             .iter()
             .map(|&i| self.captions[i].text.clone())
             .collect::<Vec<String>>()
             .join(" ");
-
-        self.captions[first_idx].text = merged_text;
-        self.captions[first_idx].end_ms = self.captions[last_idx].end_ms;
-
-        for &idx in self.selected_indices[1..].iter().rev() {
-            self.captions.remove(idx);
-        }
-
-        self.selected_indices.clear();
+        let merged_words: Vec<Word> = positions.iter().flat_map(|&i| self.captions[i].words.clone()).collect();
+        let end_ms = positions.iter().map(|&i| self.captions[i].end_ms).max().unwrap_or_default();
+
+        let first_index = positions[0];
+        let first = &mut self.captions[first_index];
+        first.text = merged_text;
+        first.end_ms = end_ms;
+        first.words = merged_words;
+        let first_id = first.id.clone();
+
+        // The earliest caption starts no later than the others, so it keeps its
+        // place among the captions that remain.
+        let selected = std::mem::take(&mut self.selected_ids);
+        let mut touched = self.remove_where(|c| c.id != first_id && selected.contains(&c.id));
+        let removed_before = positions[1..].iter().filter(|&&i| i < first_index).count();
+        touched.push(Touched::Changed(first_index, first_index - removed_before));
+
+        self.selected_ids = HashSet::from([first_id]);
+        self.history.record("Merge captions", &self.captions, &touched);
         Ok(())
     }
 
     #[wasm_bindgen]
     pub fn shift_all_captions(&mut self, shift_ms: i32) {
-        self.save_history();
         for caption in &mut self.captions {
-            caption.start_ms += shift_ms;
-            caption.end_ms += shift_ms;
+            let (start_ms, end_ms) = (caption.start_ms + shift_ms, caption.end_ms + shift_ms);
+            words::retime(&mut caption.words, (caption.start_ms, caption.end_ms), (start_ms, end_ms));
+            caption.start_ms = start_ms;
+            caption.end_ms = end_ms;
         }
+        self.history.record(&format!("Shift all {:+}ms", shift_ms), &self.captions, &self.all_changed());
     }
 
     #[wasm_bindgen]
     pub fn stretch_captions(&mut self, factor: f32) {
-        self.save_history();
         for caption in &mut self.captions {
-            caption.start_ms = (caption.start_ms as f32 * factor) as i32;
-            caption.end_ms = (caption.end_ms as f32 * factor) as i32;
+            let start_ms = (caption.start_ms as f32 * factor) as i32;
+            let end_ms = (caption.end_ms as f32 * factor) as i32;
+            words::retime(&mut caption.words, (caption.start_ms, caption.end_ms), (start_ms, end_ms));
+            caption.start_ms = start_ms;
+            caption.end_ms = end_ms;
         }
+        self.history.record(&format!("Stretch all x{}", factor), &self.captions, &self.all_changed());
+    }
+    // Selects a built-in QC profile: "netflix", "bbc", "dcmp" or "youtube".
+    #[wasm_bindgen]
+    pub fn set_qc_profile(&mut self, name: &str) -> Result<(), JsValue> {
+        self.qc_profile = qc::Profile::preset(name)?;
+        Ok(())
+    }
+
+    // Sets a custom QC profile. Limits left out keep the Netflix values; a limit
+    // set to null is not checked.
+    #[wasm_bindgen]
+    pub fn set_custom_qc_profile(&mut self, profile: JsValue) -> Result<(), JsValue> {
+        self.qc_profile = serde_wasm_bindgen::from_value(profile)?;
+        Ok(())
     }
+
+    #[wasm_bindgen]
+    pub fn get_qc_profile(&self) -> String {
+        serde_json::to_string(&self.qc_profile).unwrap_or_default()
+    }
+
+    // Shot change times in ms, e.g. from scene detection, for the shot-change rule.
+    #[wasm_bindgen]
+    pub fn set_shot_changes(&mut self, times_ms: Vec<i32>) {
+        self.shot_changes = times_ms;
+        self.shot_changes.sort_unstable();
+        self.shot_changes.dedup();
+    }
+
+    // Checks all captions against the QC profile. Returns a JSON list of
+    // `{ caption_id, rule, severity, value, limit, start_ms }` in timeline order.
+    #[wasm_bindgen]
+    pub fn run_qc(&self) -> String {
+        let issues = qc::check(&self.captions, &self.qc_profile, self.frame_rate, &self.shot_changes);
+        serde_json::to_string(&issues).unwrap_or_default()
+    }
+
+    // The reading-speed subset of run_qc: the max_cps and min_cps issues.
     #[wasm_bindgen]
     pub fn analyze_reading_speed(&self) -> String {
-        let mut warnings = Vec::new();
+        let issues: Vec<_> = qc::check(&self.captions, &self.qc_profile, self.frame_rate, &self.shot_changes)
+            .into_iter()
+            .filter(|issue| issue.rule.ends_with("_cps"))
+            .collect();
+        serde_json::to_string(&issues).unwrap_or_default()
+    }
 
-        for (i, caption) in self.captions.iter().enumerate() {
-            let duration_seconds = (caption.end_ms - caption.start_ms) as f32 / 1000.0;
-            let char_count = caption.text.chars().count() as f32;
-            let cps = char_count / duration_seconds;
-
-            if cps > 20.0 {
-                warnings.push(format!("Caption {} too fast: {:.1} chars/sec", i + 1, cps));
-            } else if cps < 5.0 && char_count > 10.0 {
-                warnings.push(format!("Caption {} too slow: {:.1} chars/sec", i + 1, cps));
+    // Re-chunks long captions into cues within `rules` ({ max_duration_ms,
+    // min_duration_ms, max_chars }; defaults 7000, 1000 and 84). Cuts fall at
+    // sentence and clause boundaries, pauses between words and silences in the
+    // loaded waveform. Runs as one undo step; returns the number of cues added.
+    #[wasm_bindgen]
+    pub fn auto_segment(&mut self, rules: JsValue) -> Result<u32, JsValue> {
+        let rules: segment::Rules = if rules.is_undefined() || rules.is_null() {
+            segment::Rules::default()
+        } else {
+            serde_wasm_bindgen::from_value(rules)?
+        };
+
+        let silent = |time_ms: i32| self.is_silent(time_ms);
+        let cues: Vec<Vec<Caption>> = self.captions.iter().map(|c| segment::segment(c, &rules, &silent)).collect();
+
+        let taken: HashSet<&str> = self.captions.iter().map(|c| c.id.as_str()).collect();
+        let mut added = 0;
+        let mut captions = Vec::with_capacity(self.captions.len());
+        let mut touched = Vec::new();
+        for (index, group) in cues.into_iter().enumerate() {
+            if group.len() > 1 {
+                touched.push(Touched::Changed(index, captions.len()));
+            }
+            for mut cue in group {
+                if cue.id.is_empty() {
+                    cue.id = Self::unused_caption_id(&mut self.caption_id_nonce, |id| taken.contains(id));
+                    touched.push(Touched::Inserted(captions.len()));
+                    added += 1;
+                }
+                captions.push(cue);
             }
         }
-        serde_json::to_string(&warnings).unwrap_or_default()
+        self.captions = captions;
+
+        self.history.record("Auto segment", &self.captions, &touched);
+        Ok(added)
+    }
+
+    // A 2D canvas context used to measure text in each caption's font when
+    // reflowing lines. Without one, line length is counted in characters.
+    #[wasm_bindgen]
+    pub fn set_text_measure_context(&mut self, context: Option<web_sys::CanvasRenderingContext2d>) {
+        self.measure_context = context;
     }
-    fn detect_conflicts(&self) -> Vec<(usize, usize)> {
-        let mut conflicts = Vec::new();
 
-        for i in 0..self.captions.len() {
-            for j in i + 1..self.captions.len() {
-                if self.captions[i].end_ms > self.captions[j].start_ms
-                    && self.captions[i].start_ms < self.captions[j].end_ms
-                {
-                    conflicts.push((i, j));
+    // Re-breaks every caption into at most `max_lines` balanced lines of up to
+    // `max_chars_per_line` characters (and, with a measure context, no wider
+    // than that many characters in the caption's font). Lines opening with a
+    // dash or a speaker label stay on their own line. Captions that cannot fit
+    // are left unchanged; their ids are returned as a JSON list so they can be
+    // split.
+    #[wasm_bindgen]
+    pub fn reflow_lines(&mut self, max_chars_per_line: usize, max_lines: usize) -> String {
+        let mut too_long = Vec::new();
+        let mut touched = Vec::new();
+
+        for (index, caption) in self.captions.iter_mut().enumerate() {
+            let metrics = match &self.measure_context {
+                Some(context) => reflow::Metrics::canvas(context, &caption.style),
+                None => reflow::Metrics::Characters,
+            };
+            match reflow::reflow(&caption.text, max_lines.max(1), max_chars_per_line, &metrics) {
+                Some(text) if text != caption.text => {
+                    words::set_text(caption, text);
+                    touched.push(Touched::Changed(index, index));
                 }
+                Some(_) => {}
+                None => too_long.push(caption.id.clone()),
             }
         }
 
-        conflicts
+        self.history.record("Reflow lines", &self.captions, &touched);
+        serde_json::to_string(&too_long).unwrap_or_default()
+    }
+
+    // Returns a JSON list of `{ first_id, second_id, overlap_ms }` for captions
+    // that overlap on the same track.
+    #[wasm_bindgen]
+    pub fn get_conflicts(&self) -> String {
+        serde_json::to_string(&qc::find_overlaps(&self.captions)).unwrap_or_default()
+    }
+
+    // Resolves overlaps on each track and leaves at least `gap_frames` frames
+    // between consecutive captions. Strategies:
+    //   "trim" - end the earlier caption before the later one starts
+    //   "push" - delay the later caption, keeping its duration
+    // A caption that would be trimmed away entirely is left alone. Returns the
+    // conflicts that remain, as get_conflicts does.
+    #[wasm_bindgen]
+    pub fn resolve_conflicts(&mut self, strategy: &str, gap_frames: u32) -> Result<String, JsValue> {
+        let push = match strategy {
+            "trim" => false,
+            "push" => true,
+            _ => return Err(JsValue::from_str(&format!("Unknown conflict strategy: {}", strategy))),
+        };
+        let gap_ms = self.frame_rate.frames_to_ms(gap_frames as i64);
+
+        let order = qc::timeline_order(&self.captions);
+        let mut changed = Vec::new();
+        for pair in order.windows(2) {
+            let (earlier, later) = (pair[0], pair[1]);
+            if self.captions[earlier].track != self.captions[later].track {
+                continue;
+            }
+            let earliest_start = self.captions[earlier].end_ms + gap_ms;
+            let later_start = self.captions[later].start_ms;
+            if later_start >= earliest_start {
+                continue;
+            }
+
+            let (index, start_ms, end_ms) = if push {
+                let caption = &self.captions[later];
+                (later, earliest_start, earliest_start + caption.end_ms - caption.start_ms)
+            } else {
+                let caption = &self.captions[earlier];
+                if later_start - gap_ms <= caption.start_ms {
+                    continue;
+                }
+                (earlier, caption.start_ms, later_start - gap_ms)
+            };
+            let caption = &mut self.captions[index];
+            words::retime(&mut caption.words, (caption.start_ms, caption.end_ms), (start_ms, end_ms));
+            caption.start_ms = start_ms;
+            caption.end_ms = end_ms;
+            changed.push(index);
+        }
+
+        let touched = self.reposition(&changed);
+        self.history.record(&format!("Resolve conflicts ({})", strategy), &self.captions, &touched);
+        Ok(self.get_conflicts())
     }
 
     #[wasm_bindgen]
@@ -309,203 +779,500 @@ impl CaptionEditor {
             "json" => self.to_json(),
             "fcpxml" => self.to_fcpxml(),
             "edl" => self.to_edl(),
+            "ttml" | "imsc1" => ttml::write(&self.captions, self.frame_rate, ttml::Profile::Imsc1Text),
+            "dfxp" | "smpte-tt" => ttml::write(&self.captions, self.frame_rate, ttml::Profile::SmpteTt),
+            "scc" => scc::write(&self.captions),
+            "sbv" => youtube::write_sbv(&self.captions),
+            "json3" => youtube::write_json3(&self.captions),
             _ => String::from("Unsupported format"),
         }
     }
 
+    // Exports as bytes in the given encoding ("utf-8", "utf-8-bom", "utf-16le" or
+    // "utf-16be") for players that need a BOM or UTF-16.
     #[wasm_bindgen]
-    pub fn undo(&mut self) -> bool {
-        if self.history_index > 0 {
-            self.history_index -= 1;
-            self.captions = self.history[self.history_index].clone();
-            true
-        } else {
-            false
+    pub fn export_captions_encoded(&self, format: &str, encoding: &str) -> Result<Vec<u8>, JsValue> {
+        match format {
+            "stl" | "ebu-stl" => self.export_captions_binary(format),
+            _ => encoding::encode(&self.export_captions(format), encoding),
         }
     }
 
+    // Binary formats are returned as bytes (a Uint8Array in JS).
     #[wasm_bindgen]
-    pub fn redo(&mut self) -> bool {
-        if self.history_index < self.history.len() - 1 {
-            self.history_index += 1;
-            self.captions = self.history[self.history_index].clone();
-            true
-        } else {
-            false
+    pub fn export_captions_binary(&self, format: &str) -> Result<Vec<u8>, JsValue> {
+        match format {
+            "stl" | "ebu-stl" => Ok(ebu_stl::write(&self.captions, self.frame_rate)),
+            _ => Err(JsValue::from_str("Unsupported format")),
         }
     }
 
-    fn save_history(&mut self) {
-        if self.history_index < self.history.len() - 1 {
-            self.history.truncate(self.history_index + 1);
-        }
+    #[wasm_bindgen]
+    pub fn undo(&mut self) -> bool {
+        self.history.undo(&mut self.captions)
+    }
+
+    #[wasm_bindgen]
+    pub fn redo(&mut self) -> bool {
+        self.history.redo(&mut self.captions)
+    }
+
+    // Groups the edits that follow, e.g. the timing updates of a drag, into one
+    // undo step named `label`. Undo and redo close an open transaction first.
+    #[wasm_bindgen]
+    pub fn begin_transaction(&mut self, label: &str) {
+        self.history.begin(label);
+    }
 
-        self.history.push_back(self.captions.clone());
-        self.history_index += 1;
+    #[wasm_bindgen]
+    pub fn commit_transaction(&mut self) {
+        self.history.commit();
+    }
+
+    // Returns JSON `{ undo, redo }` with the labels of the steps that can be
+    // undone (most recent first) and redone (next first).
+    #[wasm_bindgen]
+    pub fn get_history_labels(&self) -> String {
+        serde_json::to_string(&self.history.labels()).unwrap_or_default()
+    }
 
-        // Keep the history buffer from growing too large
-        if self.history.len() > 100 {
-            self.history.pop_front();
-            // Since we removed an item from the front, our index must shift down
-            self.history_index -= 1;
+    // Waveform sample index for a time, once a waveform and the video duration are known.
+    fn waveform_index(&self, time_ms: i32) -> Option<usize> {
+        if self.waveform_data.is_empty() || self.video_duration_ms <= 0 {
+            return None;
         }
+        let index = time_ms as i64 * self.waveform_data.len() as i64 / self.video_duration_ms as i64;
+        usize::try_from(index).ok().filter(|&i| i < self.waveform_data.len())
     }
 
-    fn find_nearest_audio_peak(&self, time_ms: i32, threshold: f32) -> Option<i32> {
-        let sample_index = (time_ms as f32 / self.video_duration_ms as f32
-            * self.waveform_data.len() as f32) as usize;
+    fn waveform_time(&self, index: usize) -> i32 {
+        (index as i64 * self.video_duration_ms as i64 / self.waveform_data.len() as i64) as i32
+    }
 
-        let search_range = 50; // Search within 50 samples
-        let start = sample_index.saturating_sub(search_range);
-        let end = (sample_index + search_range).min(self.waveform_data.len());
+    fn is_silent(&self, time_ms: i32) -> bool {
+        self.waveform_index(time_ms).is_some_and(|i| self.waveform_data[i] < self.silence_threshold)
+    }
 
-        let mut best_peak = None;
-        let mut best_value = threshold;
+    // The time nearest `time_ms`, within `window_ms`, where speech starts
+    // (silence to sound) or, with `onset` false, where it stops.
+    fn find_speech_edge(&self, time_ms: i32, window_ms: i32, onset: bool) -> Option<i32> {
+        let first = self.waveform_index((time_ms - window_ms).max(0)).unwrap_or(0);
+        let last = self
+            .waveform_index(time_ms + window_ms)
+            .unwrap_or(self.waveform_data.len().saturating_sub(1));
+        let target = self.waveform_index(time_ms)?;
+
+        (first.max(1)..=last)
+            .filter(|&i| {
+                let (before, after) = (self.waveform_data[i - 1], self.waveform_data[i]);
+                let threshold = self.silence_threshold;
+                if onset { before < threshold && after >= threshold } else { before >= threshold && after < threshold }
+            })
+            .min_by_key(|&i| i.abs_diff(target))
+            .map(|i| self.waveform_time(i))
+    }
 
-        for i in start..end {
-            if self.waveform_data[i] > best_value {
-                best_value = self.waveform_data[i];
-                best_peak = Some(
-                    (i as f32 / self.waveform_data.len() as f32 * self.video_duration_ms as f32)
-                        as i32,
-                );
-            }
+    // Snaps the caption at `index` to the speech edges near its in and out
+    // points, keeping the QC profile's minimum gap to the captions either side
+    // of it on its track.
+    fn snap_to_audio(&mut self, order: &[usize], position: usize, window_ms: i32) -> bool {
+        let index = order[position];
+        let caption = &self.captions[index];
+        let gap_ms = self.frame_rate.frames_to_ms(self.qc_profile.min_gap_frames.unwrap_or(0));
+        let neighbour = |p: Option<usize>| p.and_then(|p| order.get(p)).map(|&i| &self.captions[i]);
+        let floor = neighbour(position.checked_sub(1))
+            .filter(|c| c.track == caption.track && c.end_ms <= caption.start_ms)
+            .map_or(i32::MIN, |c| c.end_ms + gap_ms);
+        let ceiling = neighbour(Some(position + 1))
+            .filter(|c| c.track == caption.track && c.start_ms >= caption.end_ms)
+            .map_or(i32::MAX, |c| c.start_ms - gap_ms);
+
+        let start_ms = self
+            .find_speech_edge(caption.start_ms, window_ms, true)
+            .map_or(caption.start_ms, |t| t.max(floor));
+        let end_ms = self
+            .find_speech_edge(caption.end_ms, window_ms, false)
+            .map_or(caption.end_ms, |t| t.min(ceiling));
+        if end_ms <= start_ms || (start_ms, end_ms) == (caption.start_ms, caption.end_ms) {
+            return false;
         }
 
-        best_peak
+        let caption = &mut self.captions[index];
+        words::retime(&mut caption.words, (caption.start_ms, caption.end_ms), (start_ms, end_ms));
+        caption.start_ms = start_ms;
+        caption.end_ms = end_ms;
+        true
     }
 
-    fn find_nearest_silence(&self, time_ms: i32, threshold: f32) -> Option<i32> {
-        let sample_index = (time_ms as f32 / self.video_duration_ms as f32
-            * self.waveform_data.len() as f32) as usize;
+    // Format parsers
+    fn parse_srt_timestamp(timestamp: &str) -> Result<i32, JsValue> {
+        srt::parse_timestamp(timestamp).map_err(|e| JsValue::from_str(&e))
+    }
 
-        let search_range = 50;
-        let start = sample_index.saturating_sub(search_range);
-        let end = (sample_index + search_range).min(self.waveform_data.len());
+    fn parse_vtt(content: &str) -> Result<Vec<Caption>, JsValue> {
+        let normalized = content.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
 
-        for i in start..end {
-            if self.waveform_data[i] < threshold {
-                return Some(
-                    (i as f32 / self.waveform_data.len() as f32 * self.video_duration_ms as f32)
-                        as i32,
-                );
+        // Group lines into blocks; a whitespace-only line also ends a block.
+        let mut blocks: Vec<Vec<&str>> = Vec::new();
+        let mut current: Vec<&str> = Vec::new();
+        for line in normalized.lines() {
+            if line.trim().is_empty() {
+                if !current.is_empty() {
+                    blocks.push(std::mem::take(&mut current));
+                }
+            } else {
+                current.push(line);
             }
         }
+        if !current.is_empty() {
+            blocks.push(current);
+        }
 
-        None
-    }
+        // The header must be "WEBVTT", optionally followed by a space or tab and free text.
+        let header = blocks.first().and_then(|b| b.first()).copied().unwrap_or("");
+        let header_ok = header == "WEBVTT"
+            || header.starts_with("WEBVTT ")
+            || header.starts_with("WEBVTT\t");
+        if !header_ok {
+            return Err(JsValue::from_str("Invalid WebVTT file: missing WEBVTT header"));
+        }
 
-    // Format parsers
-    fn parse_srt(&mut self, content: &str) -> Result<(), JsValue> {
-        // Clear out any old captions before importing.
-        self.captions.clear();
-        self.caption_id_nonce = 0; // Use the correct struct field `caption_id_nonce`
+        let mut captions = Vec::new();
 
-        // SRT files use a blank line to separate caption blocks.
-        for block in content.trim().split("\n\n") {
-            // Skip any empty blocks that might result from extra newlines.
-            if block.is_empty() {
+        for block in blocks.iter().skip(1) {
+            let first = block[0];
+            if Self::is_vtt_block(first, "NOTE")
+                || Self::is_vtt_block(first, "STYLE")
+                || Self::is_vtt_block(first, "REGION")
+            {
                 continue;
             }
 
-            let lines: Vec<&str> = block.trim().lines().collect();
-
-            // A valid SRT block has at least 3 lines: index, times, and text.
-            if lines.len() >= 2 {
-                // We don't strictly need the index line.
-                let timestamp_line = lines.get(1).unwrap_or(&""); // Safely get the timestamp line
-                let parts: Vec<&str> = timestamp_line.split(" --> ").collect();
-
-                if parts.len() == 2 {
-                    // We use `?` to propagate any errors from the timestamp parser.
-                    let start_ms = Self::parse_srt_timestamp(parts[0])?;
-                    let end_ms = Self::parse_srt_timestamp(parts[1])?;
-
-                    // The rest of the lines are the caption text.
-                    let text = lines[2..].join("\n");
-
-                    // We can now create and add the new caption.
-                    let new_caption = Caption {
-                        id: format!("caption_{}", self.caption_id_nonce),
-                        start_ms,
-                        end_ms,
-                        text,
-                        speaker: None,
-                        confidence: 1.0, // Imported captions are considered confident.
-                        style: Self::default_style(),
-                    };
-                    self.caption_id_nonce += 1;
-                    self.captions.push(new_caption);
+            // The timing line is either first, or second after a cue identifier.
+            let timing_index = match block.iter().take(2).position(|l| l.contains("-->")) {
+                Some(index) => index,
+                None => {
+                    log::warn!("Skipping WebVTT block without a timing line: {:?}", first);
+                    continue;
                 }
-            }
+            };
+
+            let (start_part, rest) = block[timing_index].split_once("-->").unwrap();
+            let rest = rest.trim_start();
+            let (end_part, settings) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+            let start_ms = Self::parse_vtt_timestamp(start_part)?;
+            let end_ms = Self::parse_vtt_timestamp(end_part)?;
+
+            let mut style = Self::default_style();
+            Self::apply_vtt_settings(&mut style, settings);
+
+            let raw_text = block[timing_index + 1..].join("\n");
+            let (text, speaker) = Self::strip_vtt_markup(&raw_text, &mut style);
+
+            captions.push(Caption {
+                id: String::new(),
+                start_ms,
+                end_ms,
+                text,
+                speaker,
+                confidence: 1.0,
+                style,
+                words: Vec::new(),
+                track: None,
+            });
         }
 
-        // It's good practice to sort by start time after a full import.
-        self.captions.sort_by_key(|c| c.start_ms);
+        Ok(captions)
+    }
 
-        Ok(())
+    fn is_vtt_block(first_line: &str, keyword: &str) -> bool {
+        first_line == keyword
+            || first_line.starts_with(&format!("{} ", keyword))
+            || first_line.starts_with(&format!("{}\t", keyword))
     }
 
-    // This is a robust helper function to parse the specific SRT timestamp format.
-    // It also does not need `#[wasm_bindgen]`.
-    fn parse_srt_timestamp(timestamp: &str) -> Result<i32, JsValue> {
-        // SRT uses a comma for the millisecond separator.
-        let trimmed = timestamp.trim().replace(',', ".");
-        let parts: Vec<&str> = trimmed.split(':').collect();
-        if parts.len() != 3 {
-            return Err(JsValue::from_str(&format!(
-                "Invalid timestamp format: {}",
-                timestamp
-            )));
-        }
-
-        let hours: i32 = parts[0]
-            .parse()
-            .map_err(|_| JsValue::from_str("Invalid hours"))?;
-        let minutes: i32 = parts[1]
-            .parse()
-            .map_err(|_| JsValue::from_str("Invalid minutes"))?;
-
-        let seconds_parts: Vec<&str> = parts[2].split('.').collect();
-        let seconds: i32 = seconds_parts[0]
-            .parse()
-            .map_err(|_| JsValue::from_str("Invalid seconds"))?;
-        let milliseconds: i32 = if seconds_parts.len() > 1 {
-            // Pad with zeros if necessary (e.g., ".5" becomes ".500").
-            let ms_str = seconds_parts[1];
-            let padded_ms = format!("{:0<3}", ms_str);
-            padded_ms[..3]
-                .parse()
-                .map_err(|_| JsValue::from_str("Invalid milliseconds"))?
+    // WebVTT allows the hours to be omitted (MM:SS.mmm).
+    fn parse_vtt_timestamp(timestamp: &str) -> Result<i32, JsValue> {
+        let trimmed = timestamp.trim();
+        if trimmed.matches(':').count() == 1 {
+            Self::parse_srt_timestamp(&format!("00:{}", trimmed))
         } else {
-            0
+            Self::parse_srt_timestamp(trimmed)
+        }
+    }
+
+    // Maps `line`, `position`, `align` and `size` cue settings onto the style.
+    fn apply_vtt_settings(style: &mut CaptionStyle, settings: &str) {
+        let mut line: Option<f32> = None;
+        let mut position: Option<f32> = None;
+
+        for setting in settings.split_whitespace() {
+            let Some((key, value)) = setting.split_once(':') else {
+                continue;
+            };
+            // Alignment suffixes such as ",start" or ",center" are not modelled.
+            let value = value.split(',').next().unwrap_or("");
+
+            match key {
+                "line" => {
+                    if let Some(percent) = value.strip_suffix('%') {
+                        line = percent.parse().ok();
+                    } else if let Ok(number) = value.parse::<i32>() {
+                        // Line numbers count from the top when positive and from the bottom when negative.
+                        line = Some(if number < 0 { 100.0 } else { 0.0 });
+                    }
+                }
+                "position" => {
+                    position = value.strip_suffix('%').and_then(|p| p.parse().ok());
+                }
+                "align" => {
+                    style.alignment = match value {
+                        "start" | "left" => TextAlign::Left,
+                        "end" | "right" => TextAlign::Right,
+                        _ => TextAlign::Center,
+                    };
+                }
+                "size" => {
+                    style.width_percent = value.strip_suffix('%').and_then(|p| p.parse().ok());
+                }
+                _ => {}
+            }
+        }
+
+        let x = position.unwrap_or(50.0);
+        style.position = match line {
+            None if position.is_none() => Position::Bottom,
+            Some(y) if position.is_none() && y <= 10.0 => Position::Top,
+            Some(y) if position.is_none() && y == 50.0 => Position::Middle,
+            Some(y) if position.is_none() && y >= 90.0 => Position::Bottom,
+            y => Position::Custom(x.round() as i32, y.unwrap_or(90.0).round() as i32),
         };
+    }
+
+    // Removes cue markup, returning the plain text and the voice (<v>) speaker if present.
+    // Styles apply to a whole caption, so any <b>, <i> or <u> span turns the flag on.
+    fn strip_vtt_markup(raw: &str, style: &mut CaptionStyle) -> (String, Option<String>) {
+        let mut text = String::with_capacity(raw.len());
+        let mut speaker = None;
+        let mut rest = raw;
+
+        while let Some(open) = rest.find('<') {
+            text.push_str(&rest[..open]);
+            let Some(close) = rest[open..].find('>') else {
+                text.push_str(&rest[open..]);
+                rest = "";
+                break;
+            };
+            let tag = &rest[open + 1..open + close];
+            let name = tag.split(|c: char| c == '.' || c.is_whitespace()).next().unwrap_or("");
+            match name {
+                "v" => {
+                    let voice = tag.split_once(char::is_whitespace).map(|(_, v)| v.trim());
+                    if let Some(voice) = voice.filter(|v| !v.is_empty()) {
+                        speaker.get_or_insert_with(|| voice.to_string());
+                    }
+                }
+                "b" => style.bold = true,
+                "i" => style.italic = true,
+                "u" => style.underline = true,
+                _ => {}
+            }
+            rest = &rest[open + close + 1..];
+        }
+        text.push_str(rest);
+
+        let text = text
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&nbsp;", "\u{a0}")
+            .replace("&lrm;", "\u{200e}")
+            .replace("&rlm;", "\u{200f}")
+            .replace("&amp;", "&");
 
-        Ok(hours * 3600000 + minutes * 60000 + seconds * 1000 + milliseconds)
+        (text, speaker)
     }
 
-    fn parse_vtt(&mut self, content: &str) -> Result<(), JsValue> {
-        // Similar to SRT but with different timestamp format
-        // Implementation here
-        Ok(())
+    fn parse_ass(content: &str) -> Result<Vec<Caption>, JsValue> {
+        let events = ass::parse(content, &Self::default_style())?;
+
+        Ok(events
+            .into_iter()
+            .map(|event| Caption {
+                id: String::new(),
+                start_ms: event.start_ms,
+                end_ms: event.end_ms,
+                text: event.text,
+                speaker: event.speaker,
+                confidence: 1.0,
+                style: event.style,
+                words: Vec::new(),
+                track: None,
+            })
+            .collect())
     }
 
-    fn parse_ass(&mut self, content: &str) -> Result<(), JsValue> {
-        // Parse Advanced SubStation Alpha format
-        // Implementation here
-        Ok(())
+    // Parses any text format. Captions come back with empty ids, except JSON
+    // projects, which keep theirs where they stay unique.
+    fn parse_format(format: &str, content: &str) -> Result<Vec<Caption>, JsValue> {
+        match format {
+            "srt" => Ok(srt::parse(content, &Self::default_style(), false)?.0),
+            "vtt" => Self::parse_vtt(content),
+            "ass" => Self::parse_ass(content),
+            "json" => serde_json::from_str(content).map_err(|e| JsValue::from_str(&e.to_string())),
+            "opus" => opus::parse(content, &Self::default_style()),
+            "ttml" | "dfxp" | "imsc1" | "smpte-tt" => ttml::parse(content, &Self::default_style()),
+            "scc" => scc::parse(content, &Self::default_style()),
+            "sbv" => youtube::parse_sbv(content, &Self::default_style()),
+            "json3" => youtube::parse_json3(content, &Self::default_style()),
+            "whisper" => whisper::parse(content, &Self::default_style()),
+            _ => Err(JsValue::from_str("Unsupported format")),
+        }
     }
 
-    fn parse_json(&mut self, content: &str) -> Result<(), JsValue> {
-        self.captions =
-            serde_json::from_str(content).map_err(|e| JsValue::from_str(&e.to_string()))?;
-        Ok(())
+    // Replaces the project with freshly imported captions.
+    fn replace_captions(&mut self, imported: Vec<Caption>) -> Vec<Touched> {
+        let mut touched: Vec<Touched> = (0..self.captions.len()).map(Touched::Removed).collect();
+        self.captions.clear();
+        self.selected_ids.clear();
+        self.caption_id_nonce = 0;
+        touched.extend(self.add_imported(imported));
+
+        let conflicts = qc::find_overlaps(&self.captions).len();
+        if conflicts > 0 {
+            log::warn!("Imported captions contain {} overlapping pairs", conflicts);
+        }
+        touched
     }
 
-    fn parse_opus_export(&mut self, content: &str) -> Result<(), JsValue> {
-        // Parse Opus Clip export format
-        // Implementation here
-        Ok(())
+    // Adds imported captions in start-time order, keeping ids unique, and
+    // returns where they were inserted.
+    fn add_imported(&mut self, imported: Vec<Caption>) -> Vec<Touched> {
+        let mut taken: HashSet<String> = self.captions.iter().map(|c| c.id.clone()).collect();
+        let first = self.captions.len();
+        for mut caption in imported {
+            if caption.id.is_empty() || taken.contains(&caption.id) {
+                caption.id = Self::unused_caption_id(&mut self.caption_id_nonce, |id| taken.contains(id));
+            }
+            taken.insert(caption.id.clone());
+            self.captions.push(caption);
+        }
+        let added: Vec<usize> = (first..self.captions.len()).collect();
+        self.reposition(&added)
+            .into_iter()
+            .map(|touched| match touched {
+                Touched::Changed(_, to) => Touched::Inserted(to),
+                other => other,
+            })
+            .collect()
+    }
+
+    // Replaces the caption at `index` with two halves cut at byte `offset` of its
+    // text and at `split_ms`. Whitespace at the cut is dropped; the second half
+    // gets a fresh id, which is returned.
+    fn split_caption_parts(
+        &mut self,
+        index: usize,
+        offset: usize,
+        split_ms: i32,
+        first_words: Vec<Word>,
+        second_words: Vec<Word>,
+    ) -> String {
+        let id = self.next_caption_id();
+        let original = &mut self.captions[index];
+
+        let mut second = original.clone();
+        second.id = id.clone();
+        second.start_ms = split_ms;
+        second.text = original.text[offset..].trim_start().to_string();
+        second.words = second_words;
+
+        original.end_ms = split_ms;
+        original.text = original.text[..offset].trim_end().to_string();
+        original.words = first_words;
+
+        self.captions.insert(index + 1, second);
+        id
+    }
+
+    // Positions of the selected captions that still exist, ordered by start time.
+    fn selected_positions(&self) -> Vec<usize> {
+        let mut positions: Vec<usize> = (0..self.captions.len())
+            .filter(|&i| self.selected_ids.contains(&self.captions[i].id))
+            .collect();
+        positions.sort_by_key(|&i| (self.captions[i].start_ms, i));
+        positions
+    }
+
+    // Moves the captions at `positions`, just edited in place, to their places in
+    // start-time order. The others keep their order. Returns where each moved
+    // caption was and now is.
+    fn reposition(&mut self, positions: &[usize]) -> Vec<Touched> {
+        let mut positions = positions.to_vec();
+        positions.sort_unstable();
+        positions.dedup();
+        if positions.is_empty() {
+            return Vec::new();
+        }
+
+        let mut moving = Vec::with_capacity(positions.len());
+        let mut staying = Vec::with_capacity(self.captions.len());
+        let mut next = positions.iter().peekable();
+        for (index, caption) in self.captions.drain(..).enumerate() {
+            if next.next_if_eq(&&index).is_some() {
+                moving.push((index, caption));
+            } else {
+                staying.push(caption);
+            }
+        }
+        moving.sort_by_key(|(_, c)| c.start_ms);
+
+        let mut touched = Vec::with_capacity(moving.len());
+        let mut staying = staying.into_iter().peekable();
+        for (from, caption) in moving {
+            while let Some(other) = staying.next_if(|c| c.start_ms <= caption.start_ms) {
+                self.captions.push(other);
+            }
+            touched.push(Touched::Changed(from, self.captions.len()));
+            self.captions.push(caption);
+        }
+        self.captions.extend(staying);
+        touched
+    }
+
+    // Removes the captions matching `predicate`, returning where they were.
+    fn remove_where(&mut self, predicate: impl Fn(&Caption) -> bool) -> Vec<Touched> {
+        let mut touched = Vec::new();
+        let mut index = 0;
+        self.captions.retain(|c| {
+            let remove = predicate(c);
+            if remove {
+                touched.push(Touched::Removed(index));
+            }
+            index += 1;
+            !remove
+        });
+        touched
+    }
+
+    fn all_changed(&self) -> Vec<Touched> {
+        (0..self.captions.len()).map(|i| Touched::Changed(i, i)).collect()
+    }
+
+    // The next "caption_N" id not already in use (JSON projects bring their own ids).
+    fn next_caption_id(&mut self) -> String {
+        let captions = &self.captions;
+        Self::unused_caption_id(&mut self.caption_id_nonce, |id| captions.iter().any(|c| c.id == id))
+    }
+
+    fn unused_caption_id(nonce: &mut u32, taken: impl Fn(&str) -> bool) -> String {
+        loop {
+            let id = format!("caption_{}", nonce);
+            *nonce += 1;
+            if !taken(&id) {
+                return id;
+            }
+        }
     }
 
     // Format exporters
@@ -553,8 +1320,6 @@ impl CaptionEditor {
     }
     #[wasm_bindgen]
     pub fn auto_punctuate(&mut self) {
-        self.save_history();
-
         for caption in &mut self.captions {
             let mut text = caption.text.clone();
 
@@ -579,18 +1344,18 @@ impl CaptionEditor {
             text = text.replace(" .", ".");
             text = text.replace("  ", " ");
 
-            caption.text = text;
+            words::set_text(caption, text);
         }
+        self.history.record("Auto punctuate", &self.captions, &self.all_changed());
     }
 
     // Find and replace across all captions
     #[wasm_bindgen]
     pub fn find_replace(&mut self, find: &str, replace: &str, case_sensitive: bool) {
-        self.save_history();
-
         for caption in &mut self.captions {
             if case_sensitive {
-                caption.text = caption.text.replace(find, replace);
+                let text = caption.text.replace(find, replace);
+                words::set_text(caption, text);
             } else {
                 let lower_text = caption.text.to_lowercase();
                 let lower_find = find.to_lowercase();
@@ -604,19 +1369,19 @@ impl CaptionEditor {
                 }
                 result.push_str(&caption.text[last_end..]);
 
-                caption.text = result;
+                words::set_text(caption, result);
             }
         }
+        self.history.record(&format!("Replace \"{}\"", find), &self.captions, &self.all_changed());
     }
 
     // Apply profanity filter
     #[wasm_bindgen]
     pub fn apply_profanity_filter(&mut self, bleep: bool) {
-        self.save_history();
-
         let profanity_list = vec!["fuck", "shit", "damn", "hell", "ass"];
 
         for caption in &mut self.captions {
+            let original = caption.text.clone();
             for word in &profanity_list {
                 let replacement = if bleep {
                     "[bleep]"
@@ -633,7 +1398,10 @@ impl CaptionEditor {
                 );
                 caption.text = caption.text.replace(&capitalized, &replacement);
             }
+            let filtered = std::mem::replace(&mut caption.text, original);
+            words::set_text(caption, filtered);
         }
+        self.history.record("Profanity filter", &self.captions, &self.all_changed());
     }
     fn format_vtt_timestamp(ms: i32) -> String {
         let hours = ms / 3600000;
@@ -648,8 +1416,7 @@ impl CaptionEditor {
     }
 
     fn to_ass(&self) -> String {
-        // Export to Advanced SubStation Alpha format
-        String::from("[Script Info]\n[V4+ Styles]\n[Events]\n")
+        ass::write(&self.captions)
     }
 
     fn to_json(&self) -> String {
@@ -657,18 +1424,14 @@ impl CaptionEditor {
     }
 
     fn to_fcpxml(&self) -> String {
-        // Export to Final Cut Pro XML
-        String::from(
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<fcpxml version=\"1.9\">\n</fcpxml>",
-        )
+        fcpxml::write(&self.captions, self.frame_rate, self.video_duration_ms)
     }
 
     fn to_edl(&self) -> String {
-        // Export to Edit Decision List
-        String::from("TITLE: Caption EDL\nFCM: NON-DROP FRAME\n\n")
+        edl::write(&self.captions, self.frame_rate)
     }
 
-    fn default_style() -> CaptionStyle {
+    pub(crate) fn default_style() -> CaptionStyle {
         CaptionStyle {
             position: Position::Bottom,
             font_size: 16,
@@ -689,6 +1452,413 @@ impl CaptionEditor {
             shadow_blur: 0.0,
             border_radius: 0.0,
             letter_spacing: 0.0,
+            width_percent: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::caption;
+
+    // CaptionEditor::new logs to the browser console, which needs wasm.
+    fn editor(captions: Vec<Caption>) -> CaptionEditor {
+        CaptionEditor {
+            history: History::new(&captions),
+            captions,
+            selected_ids: HashSet::new(),
+            caption_id_nonce: 0,
+            waveform_data: Vec::new(),
+            silence_threshold: 0.0,
+            video_duration_ms: 0,
+            frame_rate: FrameRate::default(),
+            qc_profile: qc::netflix(),
+            shot_changes: Vec::new(),
+            measure_context: None,
+            playback_rate: 1.0,
+            auto_save_enabled: true,
+        }
+    }
+
+    // One edit in a sequence replayed by the undo tests.
+    type Step = Box<dyn Fn(&mut CaptionEditor)>;
+
+    fn ids(editor: &CaptionEditor) -> Vec<&str> {
+        editor.captions.iter().map(|c| c.id.as_str()).collect()
+    }
+
+    #[test]
+    fn merge_import_keeps_ids_unique() {
+        let mut editor = editor(vec![caption("caption_0", 0, 1000, "One"), caption("caption_2", 1000, 2000, "Two")]);
+        let imported = vec![
+            caption("caption_0", 3000, 4000, "Three"),
+            caption("", 4000, 5000, "Four"),
+            caption("mine", 5000, 6000, "Five"),
+            caption("mine", 6000, 7000, "Six"),
+        ];
+        let content = serde_json::to_string(&imported).unwrap();
+        editor.import_captions_merge("json", &content, 0, None).unwrap();
+
+        assert_eq!(ids(&editor), ["caption_0", "caption_2", "caption_1", "caption_3", "mine", "caption_4"]);
+    }
+
+    #[test]
+    fn merge_import_offsets_into_a_track_and_reports_conflicts() {
+        let on_track = |id: &str, start_ms: i32, end_ms: i32, track: &str| Caption {
+            track: Some(track.to_string()),
+            ..caption(id, start_ms, end_ms, id)
+        };
+        let mut editor = editor(vec![
+            caption("main", 0, 2000, "main"),
+            on_track("en_1", 2000, 4000, "en"),
+            on_track("en_2", 6000, 8000, "en"),
+        ]);
+        let mut first = caption("new_1", 0, 1000, "new one");
+        first.words = vec![Word { text: "new".into(), start_ms: 0, end_ms: 400, confidence: 1.0 }];
+        let imported = vec![first, caption("new_2", 3000, 4000, "new two"), on_track("new_3", 5000, 7000, "fr")];
+        let content = serde_json::to_string(&imported).unwrap();
+
+        let conflicts = editor.import_captions_merge("json", &content, 1500, Some("en".to_string())).unwrap();
+
+        let find = |id: &str| editor.captions.iter().find(|c| c.id == id).unwrap();
+        let timings: Vec<(i32, i32)> =
+            ["new_1", "new_2", "new_3"].iter().map(|id| (find(id).start_ms, find(id).end_ms)).collect();
+        assert_eq!(timings, [(1500, 2500), (4500, 5500), (6500, 8500)]);
+        assert_eq!((find("new_1").words[0].start_ms, find("new_1").words[0].end_ms), (1500, 1900));
+        assert!(["new_1", "new_2", "new_3"].iter().all(|id| find(id).track.as_deref() == Some("en")));
+        assert_eq!(find("main").track, None);
+        // "new_1" also overlaps "main" in time, but that caption is on the default track.
+        let conflicts: serde_json::Value = serde_json::from_str(&conflicts).unwrap();
+        assert_eq!(
+            conflicts,
+            serde_json::json!([
+                { "imported_id": "new_1", "existing_id": "en_1" },
+                { "imported_id": "new_3", "existing_id": "en_2" },
+            ])
+        );
+    }
+
+    #[test]
+    fn edits_to_scattered_captions_undo_and_redo() {
+        let captions = (0..6).map(|i| caption(&format!("c{}", i), i * 1000, i * 1000 + 900, "text")).collect();
+        let mut editor = editor(captions);
+        let select = |editor: &mut CaptionEditor, ids: &[&str]| {
+            editor.selected_ids = ids.iter().map(|id| id.to_string()).collect();
+        };
+        let mut snapshots = vec![editor.captions.clone()];
+
+        // Each edit touches captions that are not next to each other: c1 moves
+        // past c4 with c3, c0 moves past c4, and c4 merges with c5 around c0.
+        select(&mut editor, &["c1", "c3"]);
+        editor.shift_selected(3500);
+        assert_eq!(ids(&editor), ["c0", "c2", "c4", "c1", "c5", "c3"]);
+        snapshots.push(editor.captions.clone());
+
+        select(&mut editor, &["c0", "c5"]);
+        editor.shift_selected(-200);
+        snapshots.push(editor.captions.clone());
+        editor.update_caption_timing("c0", 4600, 4700);
+        snapshots.push(editor.captions.clone());
+
+        select(&mut editor, &["c2", "c1", "c3"]);
+        editor.delete_selected();
+        assert_eq!(ids(&editor), ["c4", "c0", "c5"]);
+        snapshots.push(editor.captions.clone());
+
+        select(&mut editor, &["c4", "c5"]);
+        editor.merge_selected().unwrap();
+        assert_eq!(ids(&editor), ["c4", "c0"]);
+        snapshots.push(editor.captions.clone());
+
+        for snapshot in snapshots.iter().rev().skip(1) {
+            assert!(editor.undo());
+            assert_eq!(&editor.captions, snapshot);
+        }
+        for snapshot in &snapshots[1..] {
+            assert!(editor.redo());
+            assert_eq!(&editor.captions, snapshot);
+        }
+    }
+
+    // Overlapping captions on the default track, a sign that overlaps all of them
+    // on its own track, and on a third track a caption that trimming would erase.
+    fn conflicting() -> CaptionEditor {
+        let on_track = |id: &str, start_ms: i32, end_ms: i32, track: &str| Caption {
+            track: Some(track.to_string()),
+            ..caption(id, start_ms, end_ms, id)
+        };
+        let mut editor = editor(vec![
+            caption("a", 0, 2000, "a"),
+            on_track("sign", 0, 5000, "signs"),
+            on_track("fr_1", 0, 500, "fr"),
+            on_track("fr_2", 0, 1000, "fr"),
+            caption("b", 1500, 3000, "b"),
+            caption("c", 2900, 4000, "c"),
+        ]);
+        editor.set_frame_rate(29.97, false).unwrap();
+        editor
+    }
+
+    // Caption timings ordered by id, as captions starting together may swap places.
+    fn timings(editor: &CaptionEditor) -> Vec<(&str, i32, i32)> {
+        let mut timings: Vec<_> = editor.captions.iter().map(|c| (c.id.as_str(), c.start_ms, c.end_ms)).collect();
+        timings.sort_unstable();
+        timings
+    }
+
+    fn conflict(first_id: &str, second_id: &str, overlap_ms: i32) -> serde_json::Value {
+        serde_json::json!({ "first_id": first_id, "second_id": second_id, "overlap_ms": overlap_ms })
+    }
+
+    #[test]
+    fn reports_overlaps_on_the_same_track() {
+        let conflicts: serde_json::Value = serde_json::from_str(&conflicting().get_conflicts()).unwrap();
+        assert_eq!(
+            conflicts,
+            serde_json::json!([conflict("a", "b", 500), conflict("b", "c", 100), conflict("fr_1", "fr_2", 500)])
+        );
+    }
+
+    #[test]
+    fn trimming_ends_captions_a_gap_before_the_next() {
+        let mut editor = conflicting();
+        let before = editor.captions.clone();
+        // Two frames at 29.97 fps is 66.7ms, rounded to 67.
+        let remaining = editor.resolve_conflicts("trim", 2).unwrap();
+
+        let remaining: serde_json::Value = serde_json::from_str(&remaining).unwrap();
+        assert_eq!(remaining, serde_json::json!([conflict("fr_1", "fr_2", 500)]));
+        assert_eq!(
+            timings(&editor),
+            [
+                ("a", 0, 1433),
+                ("b", 1500, 2833),
+                ("c", 2900, 4000),
+                ("fr_1", 0, 500),
+                ("fr_2", 0, 1000),
+                ("sign", 0, 5000),
+            ]
+        );
+
+        assert!(editor.undo());
+        assert_eq!(editor.captions, before);
+    }
+
+    #[test]
+    fn pushing_delays_captions_a_gap_after_the_previous() {
+        let mut editor = conflicting();
+        let remaining = editor.resolve_conflicts("push", 2).unwrap();
+
+        assert_eq!(remaining, "[]");
+        assert_eq!(
+            timings(&editor),
+            [
+                ("a", 0, 2000),
+                ("b", 2067, 3567),
+                ("c", 3634, 4734),
+                ("fr_1", 0, 500),
+                ("fr_2", 567, 1567),
+                ("sign", 0, 5000),
+            ]
+        );
+    }
+
+    fn with_words(id: &str, start_ms: i32, end_ms: i32, words: &[(&str, i32, i32)]) -> Caption {
+        let text = words.iter().map(|(text, _, _)| *text).collect::<Vec<_>>().join(" ");
+        let mut caption = caption(id, start_ms, end_ms, &text);
+        caption.words = words
+            .iter()
+            .map(|&(text, start_ms, end_ms)| Word { text: text.to_string(), start_ms, end_ms, confidence: 1.0 })
+            .collect();
+        caption
+    }
+
+    fn word_timings(caption: &Caption) -> Vec<(&str, i32, i32)> {
+        caption.words.iter().map(|w| (w.text.as_str(), w.start_ms, w.end_ms)).collect()
+    }
+
+    const QUICK_FOX: [(&str, i32, i32); 4] =
+        [("the", 0, 200), ("quick", 300, 600), ("brown", 700, 1000), ("fox", 1100, 1400)];
+
+    #[test]
+    fn splits_between_words_at_a_time() {
+        let mut editor = editor(vec![with_words("fox", 0, 1500, &QUICK_FOX)]);
+        editor.split_caption("fox", 650).unwrap();
+
+        let (first, second) = (&editor.captions[0], &editor.captions[1]);
+        assert_eq!((first.text.as_str(), first.end_ms), ("the quick", 650));
+        assert_eq!(word_timings(first), [("the", 0, 200), ("quick", 300, 600)]);
+        assert_eq!((second.text.as_str(), second.start_ms), ("brown fox", 650));
+        assert_eq!(word_timings(second), [("brown", 700, 1000), ("fox", 1100, 1400)]);
+    }
+
+    #[test]
+    fn splits_inside_a_word_at_the_text_cursor() {
+        let mut editor = editor(vec![with_words("fox", 0, 1500, &QUICK_FOX)]);
+        let id = editor.split_caption_at_text("fox", 6).unwrap();
+
+        let (first, second) = (&editor.captions[0], &editor.captions[1]);
+        assert_eq!(second.id, id);
+        assert_eq!((first.text.as_str(), first.end_ms), ("the qu", 420));
+        assert_eq!(word_timings(first), [("the", 0, 200), ("qu", 300, 420)]);
+        assert_eq!((second.text.as_str(), second.start_ms), ("ick brown fox", 420));
+        assert_eq!(word_timings(second)[0], ("ick", 420, 600));
+    }
+
+    #[test]
+    fn merging_keeps_the_words_of_each_caption() {
+        let mut editor = editor(vec![
+            with_words("a", 0, 650, &QUICK_FOX[..2]),
+            with_words("b", 650, 1500, &QUICK_FOX[2..]),
+            caption("c", 2000, 3000, "later"),
+        ]);
+        editor.select_range(0, 1000);
+        editor.merge_selected().unwrap();
+
+        assert_eq!(ids(&editor), ["a", "c"]);
+        let merged = &editor.captions[0];
+        assert_eq!((merged.text.as_str(), merged.start_ms, merged.end_ms), ("the quick brown fox", 0, 1500));
+        assert_eq!(word_timings(merged), QUICK_FOX);
+        assert!(words::matches_text(&merged.words, &merged.text));
+    }
+
+    #[test]
+    fn every_edit_undoes_and_redoes_to_its_snapshot() {
+        let mut editor = editor(Vec::new());
+        let mut snapshots = vec![editor.captions.clone()];
+        let steps: Vec<Step> = vec![
+            Box::new(|e| e.add_caption(1000, 2000, "the second one")),
+            Box::new(|e| e.add_caption(0, 900, "first")),
+            Box::new(|e| e.add_caption(3000, 5000, "a third caption that is long enough to wrap onto two lines")),
+            Box::new(|e| {
+                e.create_caption(2500);
+            }),
+            Box::new(|e| e.update_caption_text("caption_3", "fourth")),
+            Box::new(|e| e.update_caption_timing("caption_3", 6000, 7000)),
+            Box::new(|e| e.split_caption("caption_2", 4000).unwrap()),
+            Box::new(|e| {
+                e.split_caption_at_text("caption_0", 4).unwrap();
+            }),
+            Box::new(|e| {
+                e.select_range(0, 1600);
+                e.shift_selected(2200);
+            }),
+            Box::new(|e| {
+                e.select_range(3000, 3700);
+                e.merge_selected().unwrap();
+            }),
+            Box::new(|e| {
+                e.reflow_lines(20, 2);
+            }),
+            Box::new(|e| {
+                e.resolve_conflicts("push", 2).unwrap();
+            }),
+            Box::new(|e| e.shift_all_captions(-100)),
+            Box::new(|e| e.stretch_captions(1.5)),
+            Box::new(|e| e.auto_punctuate()),
+            Box::new(|e| e.find_replace("second", "2nd", false)),
+            Box::new(|e| {
+                e.select_range(6000, 7000);
+                e.delete_selected();
+            }),
+            Box::new(|e| {
+                let imported = vec![caption("", 500, 800, "early"), caption("", 20_000, 21_000, "late")];
+                let content = serde_json::to_string(&imported).unwrap();
+                e.import_captions_merge("json", &content, 0, None).unwrap();
+            }),
+            Box::new(|e| {
+                let imported = vec![caption("", 0, 500, "replaced")];
+                e.import_captions("json", &serde_json::to_string(&imported).unwrap()).unwrap();
+            }),
+        ];
+        for step in &steps {
+            step(&mut editor);
+            assert_ne!(snapshots.last(), Some(&editor.captions), "step {} changed nothing", snapshots.len());
+            snapshots.push(editor.captions.clone());
+            let mut sorted = editor.captions.clone();
+            sorted.sort_by_key(|c| c.start_ms);
+            assert_eq!(editor.captions, sorted);
+        }
+
+        for snapshot in snapshots.iter().rev().skip(1) {
+            assert!(editor.undo());
+            assert_eq!(&editor.captions, snapshot);
         }
+        assert!(!editor.undo());
+        for snapshot in snapshots.iter().skip(1) {
+            assert!(editor.redo());
+            assert_eq!(&editor.captions, snapshot);
+        }
+    }
+
+    #[test]
+    fn snapping_keeps_the_minimum_gap_and_timeline_order() {
+        let mut other_track = caption("c", 1150, 2000, "Other speaker");
+        other_track.track = Some(String::from("speaker2"));
+        let mut editor = editor(vec![caption("a", 0, 1000, "One"), other_track, caption("b", 1200, 2500, "Two")]);
+        // 100 ms samples: speech until 900 ms and again from 1000 to 2500 ms.
+        editor.waveform_data = (0..100).map(|i| if i == 9 || i >= 25 { 0.0 } else { 1.0 }).collect();
+        editor.silence_threshold = 0.1;
+        editor.video_duration_ms = 10_000;
+
+        assert!(editor.snap_caption_to_audio("b", 300).unwrap());
+        // Netflix asks for 2 frames (67 ms at 30 fps) after "a", not the onset at 1000 ms.
+        assert_eq!((editor.captions[1].start_ms, editor.captions[1].end_ms), (1067, 2500));
+        assert_eq!(ids(&editor), ["a", "b", "c"]);
+
+        assert!(editor.undo());
+        assert_eq!(ids(&editor), ["a", "c", "b"]);
+        assert_eq!(editor.captions[2].start_ms, 1200);
+    }
+
+    #[test]
+    fn parses_webvtt_cues_and_round_trips_them() {
+        let content = "\u{feff}WEBVTT - Example\r\n\r\nNOTE written by hand\r\n\r\nSTYLE\r\n::cue { color: yellow }\r\n\r\n\
+            intro\r\n00:01.000 --> 00:03.500 line:0 align:left\r\n<v Anna>Hello &amp; <b>welcome</b></v>\r\n\r\n\
+            01:00:04.000 --> 01:00:06.250 position:20% line:80% size:40%\r\nSecond line\r\nof text\r\n";
+        let captions = CaptionEditor::parse_vtt(content).unwrap();
+
+        assert_eq!(captions.len(), 2);
+        let (first, second) = (&captions[0], &captions[1]);
+        assert_eq!((first.start_ms, first.end_ms), (1000, 3500));
+        assert_eq!(first.text, "Hello & welcome");
+        assert_eq!(first.speaker.as_deref(), Some("Anna"));
+        assert!(first.style.bold);
+        assert_eq!(first.style.position, Position::Top);
+        assert_eq!(first.style.alignment, TextAlign::Left);
+        assert_eq!((second.start_ms, second.end_ms), (3_604_000, 3_606_250));
+        assert_eq!(second.text, "Second line\nof text");
+        assert_eq!(second.style.position, Position::Custom(20, 80));
+        assert_eq!(second.style.width_percent, Some(40.0));
+
+        let reparsed = CaptionEditor::parse_vtt(&editor(captions.clone()).to_vtt()).unwrap();
+        let timing_and_text = |c: &Caption| (c.start_ms, c.end_ms, c.text.clone());
+        assert_eq!(
+            reparsed.iter().map(timing_and_text).collect::<Vec<_>>(),
+            captions.iter().map(timing_and_text).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn selection_follows_ids_through_edits() {
+        let mut editor = editor((0..5).map(|i| caption(&format!("c{}", i), i * 1000, i * 1000 + 900, "x")).collect());
+        editor.select_range(1500, 3500);
+        assert_eq!(editor.get_selection(), r#"["c1","c2","c3"]"#);
+
+        editor.shift_selected(10_000);
+        assert_eq!(ids(&editor), ["c0", "c4", "c1", "c2", "c3"]);
+        assert_eq!(editor.get_selection(), r#"["c1","c2","c3"]"#);
+
+        editor.merge_selected().unwrap();
+        assert_eq!(ids(&editor), ["c0", "c4", "c1"]);
+        assert_eq!((editor.captions[2].start_ms, editor.captions[2].end_ms), (11_000, 13_900));
+        assert_eq!(editor.captions[2].text, "x x x");
+        assert_eq!(editor.get_selection(), r#"["c1"]"#);
+
+        editor.select_all();
+        editor.delete_selected();
+        assert!(editor.captions.is_empty());
+        assert_eq!(editor.get_selection(), "[]");
     }
 }
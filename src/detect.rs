@@ -0,0 +1,128 @@
+use crate::srt;
+use serde_json::Value;
+
+// Guesses the format of a caption file from its content, returning a format
+// name accepted by `import_captions` (or "fcpxml", which is export-only).
+pub fn detect_format(content: &str) -> Option<&'static str> {
+    let content = content.trim_start_matches('\u{feff}').trim_start();
+
+    if content.starts_with("WEBVTT") {
+        return Some("vtt");
+    }
+    if content.starts_with("Scenarist_SCC") {
+        return Some("scc");
+    }
+    if content.starts_with('<') {
+        return match xml_root_name(content)? {
+            "tt" => Some("ttml"),
+            "fcpxml" => Some("fcpxml"),
+            _ => None,
+        };
+    }
+    // ASS files also open with '[', but their section header is not valid JSON.
+    if (content.starts_with('{') || content.starts_with('['))
+        && let Ok(json) = serde_json::from_str::<Value>(content)
+    {
+        return detect_json(&json);
+    }
+
+    let mut lines = content.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.next()?;
+    if first.eq_ignore_ascii_case("[Script Info]") || first.eq_ignore_ascii_case("[V4+ Styles]") {
+        return Some("ass");
+    }
+    if first.chars().all(|c| c.is_ascii_digit()) && lines.next().is_some_and(|line| line.contains("-->")) {
+        return Some("srt");
+    }
+    // SBV opens with a "H:MM:SS.mmm,H:MM:SS.mmm" timing line.
+    if let Some((start, end)) = first.split_once(',')
+        && [start, end].iter().all(|t| t.contains('.') && srt::parse_timestamp(t).is_ok())
+    {
+        return Some("sbv");
+    }
+
+    None
+}
+
+// The local name of the first element, skipping the XML declaration, comments and DOCTYPE.
+fn xml_root_name(content: &str) -> Option<&str> {
+    let mut rest = content;
+    loop {
+        let start = rest.find('<')?;
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = &comment[comment.find("-->")? + 3..];
+        } else if rest.starts_with('?') || rest.starts_with('!') {
+            rest = &rest[rest.find('>')? + 1..];
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || c == '>' || c == '/')?;
+            let name = &rest[..end];
+            return Some(name.rsplit(':').next().unwrap_or(name));
+        }
+    }
+}
+
+fn detect_json(json: &Value) -> Option<&'static str> {
+    let is_caption = |item: &Value| {
+        ["start_ms", "end_ms", "text", "style"]
+            .iter()
+            .all(|key| item.get(*key).is_some())
+    };
+    let is_opus_export = |clips: &Value| {
+        clips.as_array().is_some_and(|clips| clips.iter().all(|c| c.get("segments").is_some_and(Value::is_array)))
+    };
+
+    match json {
+        Value::Array(items) if items.is_empty() || items.iter().all(is_caption) => Some("json"),
+        Value::Object(map) if map.get("events").is_some_and(Value::is_array) => Some("json3"),
+        Value::Object(map) if map.get("transcription").is_some_and(Value::is_array) => Some("whisper"),
+        Value::Object(map) if map.get("clips").is_some_and(is_opus_export) => Some("opus"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_each_format() {
+        let samples = [
+            ("vtt", "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nHello\n".to_string()),
+            ("srt", "1\n00:00:01,000 --> 00:00:02,000\nHello\n".to_string()),
+            ("ass", "[Script Info]\nScriptType: v4.00+\n\n[Events]\n".to_string()),
+            ("ass", "\u{feff}[V4+ Styles]\nFormat: Name, Fontname\n".to_string()),
+            (
+                "ttml",
+                "<?xml version=\"1.0\"?>\n<!-- exported -->\n<tt xmlns=\"http://www.w3.org/ns/ttml\"><body/></tt>"
+                    .to_string(),
+            ),
+            ("ttml", "<tt:tt xmlns:tt=\"http://www.w3.org/ns/ttml\"/>".to_string()),
+            ("fcpxml", "<?xml version=\"1.0\"?>\n<!DOCTYPE fcpxml>\n<fcpxml version=\"1.9\"/>".to_string()),
+            ("scc", "Scenarist_SCC V1.0\n\n00:00:01:00\t9420 9420\n".to_string()),
+            ("sbv", "0:00:01.000,0:00:02.000\nHello\n".to_string()),
+            ("json3", r#"{"wireMagic":"pb3","events":[{"tStartMs":0,"dDurationMs":1000}]}"#.to_string()),
+            ("whisper", include_str!("../tests/fixtures/whisper_cpp.json").to_string()),
+            ("opus", include_str!("../tests/fixtures/opus_clip.json").to_string()),
+            ("json", r#"[{"id":1,"start_ms":0,"end_ms":1000,"text":"Hi","style":{}}]"#.to_string()),
+            ("json", "[]".to_string()),
+        ];
+        for (format, content) in &samples {
+            assert_eq!(detect_format(content), Some(*format), "{content}");
+        }
+    }
+
+    #[test]
+    fn unrecognised_content_is_none() {
+        for content in [
+            "",
+            "just some text",
+            "<html><body/></html>",
+            r#"{"captions": []}"#,
+            r#"[{"text": "no timings"}]"#,
+            "[Not a section]\nfoo",
+        ] {
+            assert_eq!(detect_format(content), None, "{content}");
+        }
+    }
+}
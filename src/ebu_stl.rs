@@ -0,0 +1,551 @@
+use crate::structs::{Caption, CaptionStyle, Position, TextAlign};
+use crate::timecode::FrameRate;
+use encoding_rs::Encoding;
+use unicode_normalization::char::{compose, decompose_canonical};
+use wasm_bindgen::prelude::*;
+
+// EBU Tech 3264: a 1024-byte General Subtitle Information block followed by
+// 128-byte Text and Timing Information blocks.
+const GSI_SIZE: usize = 1024;
+const TTI_SIZE: usize = 128;
+const TEXT_FIELD_SIZE: usize = 112;
+
+// Teletext subtitles are placed on rows 1-23.
+const MAX_ROW: i32 = 23;
+
+// Extension block numbers.
+const LAST_BLOCK: u8 = 0xFF;
+const USER_DATA_BLOCK: u8 = 0xFE;
+
+// Text field control codes.
+const ITALIC_ON: u8 = 0x80;
+const ITALIC_OFF: u8 = 0x81;
+const UNDERLINE_ON: u8 = 0x82;
+const UNDERLINE_OFF: u8 = 0x83;
+const CR_LF: u8 = 0x8A;
+const FILLER: u8 = 0x8F;
+
+// Teletext alphanumeric colour codes 0x00-0x07.
+const COLORS: [&str; 8] = [
+    "#000000", "#FF0000", "#00FF00", "#FFFF00", "#0000FF", "#FF00FF", "#00FFFF", "#FFFFFF",
+];
+
+// Character code table 00 is ISO 6937: accented letters are a non-spacing
+// diacritic byte followed by the base letter.
+const DIACRITICS: [(u8, char); 13] = [
+    (0xC1, '\u{300}'), (0xC2, '\u{301}'), (0xC3, '\u{302}'), (0xC4, '\u{303}'), (0xC5, '\u{304}'),
+    (0xC6, '\u{306}'), (0xC7, '\u{307}'), (0xC8, '\u{308}'), (0xCA, '\u{30A}'), (0xCB, '\u{327}'),
+    (0xCD, '\u{30B}'), (0xCE, '\u{328}'), (0xCF, '\u{30C}'),
+];
+
+// Table 00 positions above 0x7F that hold a complete character.
+const LATIN_SPECIAL: &[(u8, char)] = &[
+    (0xA1, '¡'), (0xA2, '¢'), (0xA3, '£'), (0xA4, '$'), (0xA5, '¥'), (0xA7, '§'), (0xA8, '¤'),
+    (0xA9, '‘'), (0xAA, '“'), (0xAB, '«'), (0xAC, '←'), (0xAD, '↑'), (0xAE, '→'), (0xAF, '↓'),
+    (0xB0, '°'), (0xB1, '±'), (0xB2, '²'), (0xB3, '³'), (0xB4, '×'), (0xB5, 'µ'), (0xB6, '¶'),
+    (0xB7, '·'), (0xB8, '÷'), (0xB9, '’'), (0xBA, '”'), (0xBB, '»'), (0xBC, '¼'), (0xBD, '½'),
+    (0xBE, '¾'), (0xBF, '¿'), (0xD0, '―'), (0xD1, '¹'), (0xD2, '®'), (0xD3, '©'), (0xD4, '™'),
+    (0xD5, '♪'), (0xD6, '¬'), (0xD7, '¦'), (0xDC, '⅛'), (0xDD, '⅜'), (0xDE, '⅝'), (0xDF, '⅞'),
+    (0xE0, 'Ω'), (0xE1, 'Æ'), (0xE2, 'Đ'), (0xE3, 'ª'), (0xE4, 'Ħ'), (0xE6, 'Ĳ'), (0xE7, 'Ŀ'),
+    (0xE8, 'Ł'), (0xE9, 'Ø'), (0xEA, 'Œ'), (0xEB, 'º'), (0xEC, 'Þ'), (0xED, 'Ŧ'), (0xEE, 'Ŋ'),
+    (0xEF, 'ŉ'), (0xF0, 'ĸ'), (0xF1, 'æ'), (0xF2, 'đ'), (0xF3, 'ð'), (0xF4, 'ħ'), (0xF5, 'ı'),
+    (0xF6, 'ĳ'), (0xF7, 'ŀ'), (0xF8, 'ł'), (0xF9, 'ø'), (0xFA, 'œ'), (0xFB, 'ß'), (0xFC, 'þ'),
+    (0xFD, 'ŧ'), (0xFE, 'ŋ'), (0xFF, '\u{AD}'),
+];
+
+// Character code tables 01-04 are the ISO 8859 Cyrillic, Arabic, Greek and Hebrew sets.
+fn iso_table(cct: &str) -> Option<&'static Encoding> {
+    match cct {
+        "01" => Some(encoding_rs::ISO_8859_5),
+        "02" => Some(encoding_rs::ISO_8859_6),
+        "03" => Some(encoding_rs::ISO_8859_7),
+        "04" => Some(encoding_rs::ISO_8859_8),
+        _ => None,
+    }
+}
+
+// Writes a teletext STL file. STL only defines 25 and 30 fps, so 29.97/30 fps
+// projects keep their rate and everything else is timed at 25 fps.
+pub fn write(captions: &[Caption], frame_rate: FrameRate) -> Vec<u8> {
+    let (disk_format, rate) = if frame_rate.nominal_fps() == 30 {
+        ("STL30.01", FrameRate { drop_frame: false, ..frame_rate })
+    } else {
+        ("STL25.01", FrameRate { numerator: 25, denominator: 1, drop_frame: false })
+    };
+
+    let captions: Vec<&Caption> = captions.iter().filter(|c| !c.text.trim().is_empty()).collect();
+
+    // Use the first table that can represent every caption, falling back to Latin.
+    let cct = ["00", "01", "02", "03", "04"]
+        .into_iter()
+        .find(|cct| captions.iter().all(|c| encode(&c.text, cct).1))
+        .unwrap_or("00");
+
+    let mut blocks = Vec::new();
+    for (i, caption) in captions.iter().enumerate() {
+        let lines: Vec<&str> = caption.text.lines().collect();
+        let text = text_field(caption, &lines, cct);
+        let chunks = split_text_field(&text, cct);
+
+        for (ebn, chunk) in chunks.iter().enumerate() {
+            let mut block = [0u8; TTI_SIZE];
+            block[0] = 0; // subtitle group
+            block[1..3].copy_from_slice(&((i + 1) as u16).to_le_bytes());
+            block[3] = if ebn == chunks.len() - 1 { LAST_BLOCK } else { ebn as u8 };
+            block[4] = 0; // cumulative status: not cumulative
+            block[5..9].copy_from_slice(&timecode_bytes(rate, caption.start_ms));
+            block[9..13].copy_from_slice(&timecode_bytes(rate, caption.end_ms.max(caption.start_ms)));
+            block[13] = vertical_position(&caption.style.position, lines.len());
+            block[14] = match caption.style.alignment {
+                TextAlign::Left => 1,
+                TextAlign::Center => 2,
+                TextAlign::Right => 3,
+            };
+            block[15] = 0; // comment flag: subtitle data
+            block[16..16 + chunk.len()].copy_from_slice(chunk);
+            block[16 + chunk.len()..].fill(FILLER);
+            blocks.push(block);
+        }
+    }
+
+    let max_columns = captions
+        .iter()
+        .flat_map(|c| c.text.lines())
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(99);
+    let first_in_cue = captions.first().map(|c| timecode_bytes(rate, c.start_ms)).unwrap_or_default();
+
+    let mut gsi = [b' '; GSI_SIZE];
+    put(&mut gsi, 0, 3, "850"); // code page for the GSI block itself
+    put(&mut gsi, 3, 8, disk_format);
+    put(&mut gsi, 11, 1, "1"); // teletext level 1
+    put(&mut gsi, 12, 2, cct);
+    put(&mut gsi, 14, 2, "00"); // language unknown
+    put(&mut gsi, 16, 32, "Captions");
+    put(&mut gsi, 224, 6, &creation_date());
+    put(&mut gsi, 230, 6, &creation_date());
+    put(&mut gsi, 236, 2, "00");
+    put(&mut gsi, 238, 5, &format!("{:05}", blocks.len().min(99999)));
+    put(&mut gsi, 243, 5, &format!("{:05}", captions.len().min(99999)));
+    put(&mut gsi, 248, 3, "001");
+    put(&mut gsi, 251, 2, &format!("{:02}", max_columns));
+    put(&mut gsi, 253, 2, &format!("{:02}", MAX_ROW));
+    put(&mut gsi, 255, 1, "1"); // time codes are intended for use
+    put(&mut gsi, 256, 8, "00000000");
+    put(&mut gsi, 264, 8, &first_in_cue.iter().map(|b| format!("{:02}", b)).collect::<String>());
+    put(&mut gsi, 272, 1, "1");
+    put(&mut gsi, 273, 1, "1");
+
+    let mut output = Vec::with_capacity(GSI_SIZE + blocks.len() * TTI_SIZE);
+    output.extend_from_slice(&gsi);
+    for block in blocks {
+        output.extend_from_slice(&block);
+    }
+    output
+}
+
+fn put(gsi: &mut [u8], offset: usize, len: usize, value: &str) {
+    for (i, byte) in value.bytes().filter(u8::is_ascii).take(len).enumerate() {
+        gsi[offset + i] = byte;
+    }
+}
+
+fn text_field(caption: &Caption, lines: &[&str], cct: &str) -> Vec<u8> {
+    let color = COLORS[1..7]
+        .iter()
+        .position(|c| caption.style.color.to_uppercase().starts_with(c))
+        .map(|i| i as u8 + 1);
+
+    let mut field = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            field.push(CR_LF);
+        }
+        if let Some(code) = color {
+            field.push(code);
+        }
+        if caption.style.italic {
+            field.push(ITALIC_ON);
+        }
+        if caption.style.underline {
+            field.push(UNDERLINE_ON);
+        }
+        field.extend(encode(line.trim(), cct).0);
+        if caption.style.underline {
+            field.push(UNDERLINE_OFF);
+        }
+        if caption.style.italic {
+            field.push(ITALIC_OFF);
+        }
+    }
+    field
+}
+
+// Splits a text field across extension blocks (numbered 0x00-0xEF) without
+// separating a Latin diacritic from the letter it modifies.
+fn split_text_field<'a>(text: &'a [u8], cct: &str) -> Vec<&'a [u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while rest.len() > TEXT_FIELD_SIZE && chunks.len() < 0xEF {
+        let mut end = TEXT_FIELD_SIZE;
+        if cct == "00" && (0xC1..=0xCF).contains(&rest[end - 1]) {
+            end -= 1;
+        }
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    chunks.push(&rest[..rest.len().min(TEXT_FIELD_SIZE)]);
+    chunks
+}
+
+// Encodes text in the given character code table. The flag is false when some
+// characters had to be replaced.
+fn encode(text: &str, cct: &str) -> (Vec<u8>, bool) {
+    if let Some(encoding) = iso_table(cct) {
+        let (bytes, _, had_errors) = encoding.encode(text);
+        return (bytes.into_owned(), !had_errors);
+    }
+
+    let mut bytes = Vec::new();
+    let mut complete = true;
+    for c in text.chars() {
+        if c == '$' {
+            bytes.push(0xA4);
+        } else if (' '..='~').contains(&c) {
+            bytes.push(c as u8);
+        } else if let Some(&(byte, _)) = LATIN_SPECIAL.iter().find(|(_, s)| *s == c) {
+            bytes.push(byte);
+        } else if let Some(pair) = decompose_latin(c) {
+            bytes.extend(pair);
+        } else {
+            complete = false;
+            match c {
+                '–' | '—' => bytes.push(b'-'),
+                '…' => bytes.extend(b"..."),
+                '\u{A0}' => bytes.push(b' '),
+                _ => bytes.push(b'?'),
+            }
+        }
+    }
+    (bytes, complete)
+}
+
+// Splits an accented letter into a table 00 diacritic byte and an ASCII base letter.
+fn decompose_latin(c: char) -> Option<[u8; 2]> {
+    let mut parts = Vec::new();
+    decompose_canonical(c, |part| parts.push(part));
+    let [base, mark] = parts[..] else {
+        return None;
+    };
+    let (diacritic, _) = DIACRITICS.iter().find(|(_, m)| *m == mark)?;
+    base.is_ascii_alphabetic().then_some([*diacritic, base as u8])
+}
+
+// STL time codes are four binary bytes: hours, minutes, seconds, frames.
+fn timecode_bytes(rate: FrameRate, ms: i32) -> [u8; 4] {
+    let fps = rate.nominal_fps();
+    let frames = rate.ms_to_frames(ms.max(0));
+    [
+        ((frames / (fps * 3600)) % 24) as u8,
+        ((frames / (fps * 60)) % 60) as u8,
+        ((frames / fps) % 60) as u8,
+        (frames % fps) as u8,
+    ]
+}
+
+fn timecode_to_frames(rate: FrameRate, tc: &[u8]) -> i64 {
+    let fps = rate.nominal_fps();
+    ((tc[0] as i64 * 60 + tc[1] as i64) * 60 + tc[2] as i64) * fps + tc[3] as i64
+}
+
+// The teletext row of the first line, so that the block sits where the style puts it.
+fn vertical_position(position: &Position, line_count: usize) -> u8 {
+    let lines = line_count.max(1) as i32;
+    let first_row = match position {
+        Position::Top => 1,
+        Position::Middle => 12 - (lines - 1) / 2,
+        Position::Bottom => MAX_ROW - lines,
+        Position::Custom(_, y) => 1 + (y * (MAX_ROW - 1) + 50) / 100 - (lines - 1) / 2,
+    };
+    first_row.clamp(1, (MAX_ROW - lines + 1).max(1)) as u8
+}
+
+fn position_from_rows(first_row: i32, line_count: usize) -> Position {
+    let last_row = first_row + line_count.max(1) as i32 - 1;
+    let center = (first_row + last_row) as f32 / 2.0;
+    if first_row <= 3 {
+        Position::Top
+    } else if last_row >= MAX_ROW - 3 {
+        Position::Bottom
+    } else if (10.0..=13.0).contains(&center) {
+        Position::Middle
+    } else {
+        Position::Custom(50, ((center - 1.0) * 100.0 / (MAX_ROW - 1) as f32).round() as i32)
+    }
+}
+
+fn creation_date() -> String {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let now = js_sys::Date::new_0();
+        format!(
+            "{:02}{:02}{:02}",
+            now.get_utc_full_year() % 100,
+            now.get_utc_month() + 1,
+            now.get_utc_date()
+        )
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let days = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() / 86400)
+            .unwrap_or(0);
+        // Civil date from days since 1970-01-01 (proleptic Gregorian).
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+        format!("{:02}{:02}{:02}", year % 100, month, day)
+    }
+}
+
+// STL files start with a three-digit code page followed by a disk format code
+// such as "STL25.01".
+pub fn is_ebu_stl(bytes: &[u8]) -> bool {
+    bytes.len() >= GSI_SIZE && &bytes[3..6] == b"STL"
+}
+
+// Parses an STL file into captions. `frame_rate` is used when its timecode rate
+// matches the file's, so that 29.97 fps projects round-trip exactly.
+// Returned captions have empty ids; the editor assigns them.
+pub fn parse(bytes: &[u8], frame_rate: FrameRate, base: &CaptionStyle) -> Result<Vec<Caption>, JsValue> {
+    if bytes.len() < GSI_SIZE {
+        return Err(JsValue::from_str("Invalid EBU-STL file: missing GSI block"));
+    }
+    let gsi = &bytes[..GSI_SIZE];
+    let field = |offset: usize, len: usize| String::from_utf8_lossy(&gsi[offset..offset + len]).trim().to_string();
+
+    let disk_format = field(3, 8);
+    let fps: i64 = disk_format
+        .strip_prefix("STL")
+        .and_then(|rest| rest.strip_suffix(".01"))
+        .and_then(|fps| fps.parse().ok())
+        .ok_or_else(|| JsValue::from_str(&format!("Unsupported EBU-STL disk format code: {}", disk_format)))?;
+    let rate = if frame_rate.nominal_fps() == fps {
+        FrameRate { drop_frame: false, ..frame_rate }
+    } else {
+        FrameRate::from_fps(fps as f64, false)?
+    };
+
+    let cct = field(12, 2);
+    if cct != "00" && iso_table(&cct).is_none() {
+        log::warn!("Unknown EBU-STL character code table {:?}, decoding as Latin", cct);
+    }
+
+    // Time codes are often offset by the programme start, e.g. 10:00:00:00.
+    let programme_start = match (field(255, 1).as_str(), field(256, 8)) {
+        ("1", tcp) if tcp.len() == 8 && tcp.bytes().all(|b| b.is_ascii_digit()) => {
+            let digits: Vec<u8> = tcp.as_bytes().chunks(2).map(|d| (d[0] - b'0') * 10 + (d[1] - b'0')).collect();
+            timecode_to_frames(rate, &digits)
+        }
+        _ => 0,
+    };
+
+    struct Subtitle {
+        start: i64,
+        end: i64,
+        row: i32,
+        justification: u8,
+        text: Vec<u8>,
+    }
+
+    let mut subtitles: Vec<Subtitle> = Vec::new();
+    let mut pending: Option<Subtitle> = None;
+
+    for block in bytes[GSI_SIZE..].chunks_exact(TTI_SIZE) {
+        let ebn = block[3];
+        if ebn == USER_DATA_BLOCK || block[15] != 0 {
+            continue;
+        }
+
+        let subtitle = pending.get_or_insert_with(|| Subtitle {
+            start: timecode_to_frames(rate, &block[5..9]),
+            end: timecode_to_frames(rate, &block[9..13]),
+            row: block[13] as i32,
+            justification: block[14],
+            text: Vec::new(),
+        });
+        subtitle.text.extend_from_slice(&block[16..]);
+
+        if ebn == LAST_BLOCK {
+            subtitles.extend(pending.take());
+        }
+    }
+    subtitles.extend(pending.take());
+
+    let offset = if subtitles.iter().all(|s| s.start >= programme_start) { programme_start } else { 0 };
+
+    let mut captions: Vec<Caption> = subtitles
+        .into_iter()
+        .filter_map(|subtitle| {
+            let mut style = base.clone();
+            let text = decode_text_field(&subtitle.text, &cct, &mut style);
+            if text.is_empty() {
+                return None;
+            }
+
+            style.position = position_from_rows(subtitle.row, text.lines().count());
+            style.alignment = match subtitle.justification {
+                1 => TextAlign::Left,
+                2 => TextAlign::Center,
+                3 => TextAlign::Right,
+                _ => style.alignment,
+            };
+
+            let start_ms = rate.frames_to_ms(subtitle.start - offset);
+            Some(Caption {
+                id: String::new(),
+                start_ms,
+                end_ms: rate.frames_to_ms(subtitle.end - offset).max(start_ms),
+                text,
+                speaker: None,
+                confidence: 1.0,
+                style,
+                words: Vec::new(),
+                track: None,
+            })
+        })
+        .collect();
+
+    captions.sort_by_key(|c| c.start_ms);
+    Ok(captions)
+}
+
+// Decodes a text field, applying its colour and italic/underline codes to the style.
+fn decode_text_field(field: &[u8], cct: &str, style: &mut CaptionStyle) -> String {
+    let mut text = String::new();
+    let mut run = Vec::new();
+    let mut colored = false;
+
+    for &byte in field {
+        if (0x20..=0x7F).contains(&byte) || byte >= 0xA0 {
+            run.push(byte);
+            continue;
+        }
+
+        text.push_str(&decode_run(&run, cct));
+        run.clear();
+
+        match byte {
+            // Teletext spacing attributes occupy a character cell.
+            0x00..=0x07 => {
+                if !colored && byte != 0x07 {
+                    style.color = COLORS[byte as usize].to_string();
+                    colored = true;
+                }
+                text.push(' ');
+            }
+            0x08..=0x1F => text.push(' '),
+            ITALIC_ON => style.italic = true,
+            UNDERLINE_ON => style.underline = true,
+            CR_LF => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.push_str(&decode_run(&run, cct));
+
+    // Double-height rows are separated by two CR/LFs, so drop the empty lines.
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn decode_run(run: &[u8], cct: &str) -> String {
+    if let Some(encoding) = iso_table(cct) {
+        return encoding.decode_without_bom_handling(run).0.into_owned();
+    }
+
+    let mut text = String::new();
+    let mut bytes = run.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            0x20..=0x7E => text.push(byte as char),
+            0xA0 => text.push(' '),
+            0xC1..=0xCF => {
+                let Some(base) = bytes.next_if(|b| (0x20..=0x7E).contains(b)).map(char::from) else {
+                    continue;
+                };
+                let mark = DIACRITICS.iter().find(|(d, _)| *d == byte).map(|(_, m)| *m);
+                text.push(mark.and_then(|m| compose(base, m)).unwrap_or(base));
+            }
+            _ => {
+                if let Some(&(_, c)) = LATIN_SPECIAL.iter().find(|(b, _)| *b == byte) {
+                    text.push(c);
+                }
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captioneditor::CaptionEditor;
+    use crate::structs::caption;
+
+    fn round_trip(captions: &[Caption]) -> (Vec<u8>, Vec<Caption>) {
+        let rate = FrameRate::from_fps(25.0, false).unwrap();
+        let bytes = write(captions, rate);
+        let parsed = parse(&bytes, rate, &CaptionEditor::default_style()).unwrap();
+        (bytes, parsed)
+    }
+
+    #[test]
+    fn round_trips_latin_text_styles_and_timing() {
+        let mut styled = caption("1", 1000, 2520, "Café à la crème\n£5, ½ price ♪");
+        styled.style.italic = true;
+        styled.style.color = String::from("#FFFF00");
+        styled.style.alignment = TextAlign::Left;
+        let mut top = caption("2", 3_600_040, 3_602_000, "Straße");
+        top.style.position = Position::Top;
+        let long_text = "A long caption that runs past one text field,\n\
+            so the writer has to carry on with it in an extension block after the first one";
+        let long = caption("3", 3_700_000, 3_705_000, long_text);
+
+        let (bytes, parsed) = round_trip(&[styled, top, long]);
+        assert!(is_ebu_stl(&bytes));
+        assert_eq!(&bytes[3..11], b"STL25.01");
+        assert_eq!(&bytes[12..14], b"00");
+        assert_eq!(bytes.len(), GSI_SIZE + 4 * TTI_SIZE);
+
+        assert_eq!(parsed.len(), 3);
+        assert_eq!((parsed[0].start_ms, parsed[0].end_ms), (1000, 2520));
+        assert_eq!(parsed[0].text, "Café à la crème\n£5, ½ price ♪");
+        assert!(parsed[0].style.italic);
+        assert_eq!(parsed[0].style.color, "#FFFF00");
+        assert_eq!(parsed[0].style.alignment, TextAlign::Left);
+        assert_eq!(parsed[0].style.position, Position::Bottom);
+        assert_eq!((parsed[1].start_ms, parsed[1].text.as_str()), (3_600_040, "Straße"));
+        assert_eq!(parsed[1].style.position, Position::Top);
+        assert_eq!(parsed[2].text, long_text);
+        assert_eq!(parsed[2].end_ms, 3_705_000);
+    }
+
+    #[test]
+    fn picks_a_character_table_that_fits() {
+        let (bytes, parsed) = round_trip(&[caption("1", 0, 1000, "Привет, мир")]);
+        assert_eq!(&bytes[12..14], b"01");
+        assert_eq!(parsed[0].text, "Привет, мир");
+    }
+}
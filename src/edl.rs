@@ -0,0 +1,73 @@
+use crate::structs::Caption;
+use crate::timecode::FrameRate;
+
+// Writes a CMX3600 EDL with one event per caption. The caption text is carried
+// in COMMENT lines and an Avid-style locator at the event's record in point.
+pub fn write(captions: &[Caption], frame_rate: FrameRate) -> String {
+    let mut output = String::from("TITLE: Caption EDL\n");
+    output.push_str(if frame_rate.drop_frame { "FCM: DROP FRAME\n\n" } else { "FCM: NON-DROP FRAME\n\n" });
+
+    for (i, caption) in captions.iter().enumerate() {
+        let start = frame_rate.ms_to_frames(caption.start_ms.max(0));
+        let end = frame_rate.ms_to_frames(caption.end_ms).max(start + 1);
+        let record_in = frame_rate.frames_to_timecode(start);
+        let record_out = frame_rate.frames_to_timecode(end);
+
+        output.push_str(&format!(
+            "{:03}  {:<8} {:<5} {:<8} {} {} {} {}\n",
+            i + 1,
+            "AX",
+            "V",
+            "C",
+            record_in,
+            record_out,
+            record_in,
+            record_out
+        ));
+        output.push_str(&format!("* FROM CLIP NAME: {}\n", caption.id));
+        for line in caption.text.lines().filter(|l| !l.trim().is_empty()) {
+            output.push_str(&format!("* COMMENT: {}\n", line.trim()));
+        }
+        output.push_str(&format!(
+            "* LOC: {} YELLOW  {}\n\n",
+            record_in,
+            caption.text.split_whitespace().collect::<Vec<_>>().join(" ")
+        ));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::caption;
+
+    #[test]
+    fn writes_one_event_per_caption() {
+        let captions = [caption("c1", 1000, 2500, "Hello\nthere"), caption("c2", 3_600_000, 3_600_000, "Blink")];
+        let edl = write(&captions, FrameRate::from_fps(25.0, false).unwrap());
+        assert_eq!(
+            edl,
+            "TITLE: Caption EDL\n\
+             FCM: NON-DROP FRAME\n\n\
+             001  AX       V     C        00:00:01:00 00:00:02:13 00:00:01:00 00:00:02:13\n\
+             * FROM CLIP NAME: c1\n\
+             * COMMENT: Hello\n\
+             * COMMENT: there\n\
+             * LOC: 00:00:01:00 YELLOW  Hello there\n\n\
+             002  AX       V     C        01:00:00:00 01:00:00:01 01:00:00:00 01:00:00:01\n\
+             * FROM CLIP NAME: c2\n\
+             * COMMENT: Blink\n\
+             * LOC: 01:00:00:00 YELLOW  Blink\n\n"
+        );
+    }
+
+    #[test]
+    fn uses_drop_frame_timecode() {
+        let captions = [caption("c1", 60_000, 600_000, "Ten minutes")];
+        let edl = write(&captions, FrameRate::from_fps(29.97, true).unwrap());
+        assert!(edl.contains("FCM: DROP FRAME\n"));
+        assert!(edl.contains(" 00:00:59;28 00:10:00;00 00:00:59;28 00:10:00;00\n"), "{}", edl);
+    }
+}
@@ -0,0 +1,206 @@
+use encoding_rs::Encoding;
+use wasm_bindgen::prelude::*;
+
+// Legacy encodings tried when a file is neither marked with a BOM nor valid UTF-8.
+// Ties go to the earlier entry, so the more common encoding of each pair comes
+// first; Big5 in particular rarely beats GB18030 and is best given as a label.
+const CANDIDATES: [&Encoding; 9] = [
+    encoding_rs::WINDOWS_1252,
+    encoding_rs::WINDOWS_1250,
+    encoding_rs::ISO_8859_8,
+    encoding_rs::WINDOWS_1251,
+    encoding_rs::ISO_8859_7,
+    encoding_rs::SHIFT_JIS,
+    encoding_rs::EUC_KR,
+    encoding_rs::GB18030,
+    encoding_rs::BIG5,
+];
+
+// Decodes a subtitle file to UTF-8, returning the text and the name of the
+// encoding used. `label` (e.g. "shift_jis") overrides detection; a BOM is still honoured.
+pub fn decode(bytes: &[u8], label: Option<&str>) -> Result<(String, &'static str), JsValue> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        return Ok((text.into_owned(), encoding.name()));
+    }
+
+    let encoding = match label {
+        Some(label) => Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown encoding: {}", label)))?,
+        None => detect(bytes),
+    };
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+    Ok((text.into_owned(), encoding.name()))
+}
+
+fn detect(bytes: &[u8]) -> &'static Encoding {
+    // UTF-16 without a BOM: ASCII text leaves every other byte zero.
+    let zeros_at = |parity: usize| bytes.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+    let half = bytes.len() / 2;
+    if half > 0 && zeros_at(1) * 3 > half {
+        return encoding_rs::UTF_16LE;
+    }
+    if half > 0 && zeros_at(0) * 3 > half {
+        return encoding_rs::UTF_16BE;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return encoding_rs::UTF_8;
+    }
+
+    CANDIDATES
+        .iter()
+        .filter_map(|&encoding| {
+            let text = encoding.decode_without_bom_handling_and_without_replacement(bytes)?;
+            Some((encoding, plausibility(&text, encoding)))
+        })
+        .fold(None, |best: Option<(&'static Encoding, i64)>, (encoding, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((encoding, score)),
+        })
+        .map(|(encoding, _)| encoding)
+        .unwrap_or(encoding_rs::WINDOWS_1252)
+}
+
+// Scores how much a decoding looks like real text. Characters from two-byte
+// encodings count double, since they consume twice the input. Accented Latin
+// letters only count next to plain letters, as runs of them usually mean
+// Cyrillic or Greek bytes read as Latin, while other scripts, ideographs and
+// symbols wedged between letters usually mean the wrong encoding. Capitals inside a word suggest
+// the wrong single-byte alphabet (Greek accented vowels read as Cyrillic capitals).
+fn plausibility(text: &str, encoding: &'static Encoding) -> i64 {
+    let chars: Vec<char> = text.chars().collect();
+    let mut score = 0;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii() {
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1).copied();
+        let beside_ascii_letter = [prev, next].iter().flatten().any(char::is_ascii_alphabetic);
+        let inside_word = prev.is_some_and(char::is_alphabetic) && next.is_some_and(char::is_alphabetic);
+
+        score += match c as u32 {
+            // Latin-1 and Latin Extended-A/B letters
+            0xC0..=0x24F if c.is_alphabetic() => i64::from(beside_ascii_letter),
+            // Greek, Cyrillic, Hebrew, Arabic
+            0x370..=0x6FF if c.is_alphabetic() => {
+                if beside_ascii_letter { -1 } else { 1 }
+            }
+            // Kana, CJK punctuation and fullwidth forms
+            0x3000..=0x30FF | 0xFF01..=0xFF60 => 2,
+            // Hangul syllables
+            0xAC00..=0xD7AF => 2,
+            // CJK ideographs; Hanja are rare in modern Korean
+            0x4E00..=0x9FFF if encoding == encoding_rs::EUC_KR => 0,
+            0x4E00..=0x9FFF if beside_ascii_letter => -1,
+            0x4E00..=0x9FFF => 2,
+            // Common punctuation and symbols, which do not belong inside words
+            0xA0..=0xBF | 0xD7 | 0xF7 | 0x2010..=0x206F | 0x20AC | 0x2122 | 0x266A => -i64::from(inside_word),
+            // Halfwidth katakana, C1 controls, box drawing and the like
+            _ => -2,
+        };
+        if c.is_uppercase() && prev.is_some_and(char::is_lowercase) {
+            score -= 1;
+        }
+    }
+
+    score
+}
+
+// Encodes exported text. Supported encodings are "utf-8", "utf-8-bom",
+// "utf-16le" and "utf-16be"; the UTF-16 variants are always written with a BOM.
+pub fn encode(text: &str, encoding: &str) -> Result<Vec<u8>, JsValue> {
+    let utf16 = |to_bytes: fn(u16) -> [u8; 2]| {
+        std::iter::once(0xFEFF)
+            .chain(text.encode_utf16())
+            .flat_map(to_bytes)
+            .collect::<Vec<u8>>()
+    };
+
+    match encoding.to_ascii_lowercase().as_str() {
+        "utf-8" | "utf8" => Ok(text.as_bytes().to_vec()),
+        "utf-8-bom" | "utf8-bom" => Ok([&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat()),
+        "utf-16" | "utf-16le" => Ok(utf16(u16::to_le_bytes)),
+        "utf-16be" => Ok(utf16(u16::to_be_bytes)),
+        _ => Err(JsValue::from_str(&format!("Unsupported export encoding: {}", encoding))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decodes `text` after encoding it with `encoding`, without a label or BOM.
+    fn round_trip(text: &str, encoding: &'static Encoding) -> (String, &'static str) {
+        let (bytes, _, unmappable) = encoding.encode(text);
+        assert!(!unmappable, "{text} is not representable in {}", encoding.name());
+        decode(&bytes, None).unwrap()
+    }
+
+    #[test]
+    fn detects_legacy_encodings() {
+        let samples = [
+            ("Café crème brûlée, déjà vu à la façade.", encoding_rs::WINDOWS_1252),
+            ("こんにちは、世界。字幕のテストです。", encoding_rs::SHIFT_JIS),
+            ("你好，世界。这是一个字幕测试。", encoding_rs::GB18030),
+        ];
+        for (text, encoding) in samples {
+            assert_eq!(round_trip(text, encoding), (text.to_string(), encoding.name()));
+        }
+    }
+
+    #[test]
+    fn plausibility_prefers_the_right_decoding() {
+        let (bytes, _, _) = encoding_rs::SHIFT_JIS.encode("字幕のテストです。");
+        let score = |encoding: &'static Encoding| {
+            plausibility(&encoding.decode_without_bom_handling(&bytes).0, encoding)
+        };
+        assert!(score(encoding_rs::SHIFT_JIS) > score(encoding_rs::WINDOWS_1252));
+
+        let latin = "Café crème";
+        assert!(plausibility(latin, encoding_rs::WINDOWS_1252) > 0);
+        // UTF-8 Cyrillic misread as Windows-1252 scores as mojibake.
+        assert!(plausibility("ÐšÐ¾Ñ‚", encoding_rs::WINDOWS_1252) < 0);
+    }
+
+    #[test]
+    fn decodes_utf16_with_a_bom() {
+        let text = "1\n00:00:01,000 --> 00:00:02,000\nHéllo ♪\n";
+        let le = encode(text, "utf-16le").unwrap();
+        let be = encode(text, "utf-16be").unwrap();
+        assert_eq!(decode(&le, None).unwrap(), (text.to_string(), "UTF-16LE"));
+        assert_eq!(decode(&be, None).unwrap(), (text.to_string(), "UTF-16BE"));
+        // A BOM wins over a conflicting label.
+        assert_eq!(decode(&le, Some("shift_jis")).unwrap().1, "UTF-16LE");
+    }
+
+    #[test]
+    fn decodes_utf16_without_a_bom() {
+        let text = "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nHello\n";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode(&le, None).unwrap(), (text.to_string(), "UTF-16LE"));
+        assert_eq!(decode(&be, None).unwrap(), (text.to_string(), "UTF-16BE"));
+    }
+
+    #[test]
+    fn honours_labels_and_utf8() {
+        let (bytes, _, _) = encoding_rs::WINDOWS_1251.encode("Привет");
+        assert_eq!(decode(&bytes, Some(" cp1251 ")).unwrap(), ("Привет".to_string(), "windows-1251"));
+        assert_eq!(decode("Grüße".as_bytes(), None).unwrap(), ("Grüße".to_string(), "UTF-8"));
+        let with_bom = [&[0xEF, 0xBB, 0xBF][..], "Grüße".as_bytes()].concat();
+        assert_eq!(decode(&with_bom, None).unwrap(), ("Grüße".to_string(), "UTF-8"));
+    }
+
+    #[test]
+    fn encode_writes_the_expected_bom() {
+        assert_eq!(encode("Hi", "utf-8").unwrap(), b"Hi");
+        assert_eq!(encode("Hi", "UTF8").unwrap(), b"Hi");
+        assert_eq!(encode("Hi", "utf-8-bom").unwrap(), [0xEF, 0xBB, 0xBF, b'H', b'i']);
+        assert_eq!(encode("Hi", "utf8-bom").unwrap(), [0xEF, 0xBB, 0xBF, b'H', b'i']);
+        assert_eq!(encode("Hi", "utf-16le").unwrap(), [0xFF, 0xFE, b'H', 0, b'i', 0]);
+        assert_eq!(encode("Hi", "utf-16").unwrap(), [0xFF, 0xFE, b'H', 0, b'i', 0]);
+        assert_eq!(encode("Hi", "UTF-16BE").unwrap(), [0xFE, 0xFF, 0, b'H', 0, b'i']);
+    }
+}
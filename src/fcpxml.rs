@@ -0,0 +1,178 @@
+use crate::structs::{Caption, Position};
+use crate::timecode::FrameRate;
+
+// Writes an FCPXML 1.9 project whose spine is a single gap spanning the video,
+// with each caption attached to it as a frame-aligned iTT caption.
+pub fn write(captions: &[Caption], frame_rate: FrameRate, video_duration_ms: i32) -> String {
+    let duration_ms = captions
+        .iter()
+        .map(|c| c.end_ms)
+        .fold(video_duration_ms, i32::max);
+    let total_frames = frame_rate.ms_to_frames(duration_ms);
+    let tc_format = if frame_rate.drop_frame { "DF" } else { "NDF" };
+
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE fcpxml>\n\n");
+    output.push_str("<fcpxml version=\"1.9\">\n");
+    output.push_str("    <resources>\n");
+    output.push_str(&format!(
+        "        <format id=\"r1\" name=\"{}\" frameDuration=\"{}\" width=\"1920\" height=\"1080\"/>\n",
+        format_name(frame_rate),
+        rational_time(frame_rate, 1)
+    ));
+    output.push_str("    </resources>\n");
+    output.push_str("    <library>\n");
+    output.push_str("        <event name=\"CaptionFlow\">\n");
+    output.push_str("            <project name=\"Captions\">\n");
+    output.push_str(&format!(
+        "                <sequence format=\"r1\" duration=\"{}\" tcStart=\"0s\" tcFormat=\"{}\">\n",
+        rational_time(frame_rate, total_frames),
+        tc_format
+    ));
+    output.push_str("                    <spine>\n");
+    output.push_str(&format!(
+        "                        <gap name=\"Gap\" offset=\"0s\" start=\"0s\" duration=\"{}\">\n",
+        rational_time(frame_rate, total_frames)
+    ));
+
+    for (i, caption) in captions.iter().enumerate() {
+        let start = frame_rate.ms_to_frames(caption.start_ms.max(0));
+        let end = frame_rate.ms_to_frames(caption.end_ms).max(start + 1);
+        let style_id = format!("ts{}", i + 1);
+        let placement = match caption.style.position {
+            Position::Top => "top",
+            Position::Custom(_, y) if y < 50 => "top",
+            _ => "bottom",
+        };
+
+        output.push_str(&format!(
+            "                            <caption lane=\"1\" offset=\"{}\" duration=\"{}\" name=\"{}\" role=\"iTT?captionFormat=ITT.en\">\n",
+            rational_time(frame_rate, start),
+            rational_time(frame_rate, end - start),
+            escape_xml(&caption.text.replace('\n', " "))
+        ));
+        output.push_str(&format!(
+            "                                <text placement=\"{}\"><text-style ref=\"{}\">{}</text-style></text>\n",
+            placement,
+            style_id,
+            escape_xml(&caption.text)
+        ));
+        output.push_str(&format!(
+            "                                <text-style-def id=\"{}\"><text-style font=\".AppleSystemUIFont\" fontSize=\"13\" fontFace=\"Regular\" fontColor=\"1 1 1 1\" backgroundColor=\"0 0 0 1\"{}{}{}/></text-style-def>\n",
+            style_id,
+            if caption.style.bold { " bold=\"1\"" } else { "" },
+            if caption.style.italic { " italic=\"1\"" } else { "" },
+            if caption.style.underline { " underline=\"1\"" } else { "" }
+        ));
+        output.push_str("                            </caption>\n");
+    }
+
+    output.push_str("                        </gap>\n");
+    output.push_str("                    </spine>\n");
+    output.push_str("                </sequence>\n");
+    output.push_str("            </project>\n");
+    output.push_str("        </event>\n");
+    output.push_str("    </library>\n");
+    output.push_str("</fcpxml>\n");
+
+    output
+}
+
+// FCPXML expresses time as a rational number of seconds, e.g. "1001/30000s" per 29.97 frame.
+fn rational_time(frame_rate: FrameRate, frames: i64) -> String {
+    if frames == 0 {
+        return String::from("0s");
+    }
+    let numerator = frames * frame_rate.denominator;
+    if numerator % frame_rate.numerator == 0 {
+        format!("{}s", numerator / frame_rate.numerator)
+    } else {
+        format!("{}/{}s", numerator, frame_rate.numerator)
+    }
+}
+
+// e.g. FFVideoFormat1080p2997 or FFVideoFormat1080p25
+fn format_name(frame_rate: FrameRate) -> String {
+    let fps = frame_rate.fps();
+    if frame_rate.denominator == 1 {
+        format!("FFVideoFormat1080p{}", frame_rate.numerator)
+    } else {
+        format!("FFVideoFormat1080p{}", (fps * 100.0).round() as i64)
+    }
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::caption;
+
+    // Frames in an FCPXML time such as "30030/30000s", checking it is a whole number of frames.
+    fn frames(time: &str, frame_rate: FrameRate) -> i64 {
+        let time = time.strip_suffix('s').unwrap();
+        let (numerator, denominator) = time.split_once('/').unwrap_or((time, "1"));
+        let (numerator, denominator): (i64, i64) = (numerator.parse().unwrap(), denominator.parse().unwrap());
+        let scaled = numerator * frame_rate.numerator;
+        assert_eq!(scaled % (denominator * frame_rate.denominator), 0, "{} is not frame-aligned", time);
+        scaled / (denominator * frame_rate.denominator)
+    }
+
+    #[test]
+    fn writes_frame_aligned_captions_at_29_97() {
+        let frame_rate = FrameRate::from_fps(29.97, false).unwrap();
+        let mut top = caption("2", 3003, 4000, "Top & <tagged>");
+        top.style.position = Position::Top;
+        top.style.italic = true;
+        let captions = [caption("1", 1000, 2500, "First\nline"), top, caption("3", 9000, 12_000, "Past the video")];
+
+        let document = write(&captions, frame_rate, 10_000);
+        let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+        let xml = roxmltree::Document::parse_with_options(&document, options).unwrap();
+        let find = |name: &str| xml.descendants().find(|n| n.has_tag_name(name)).unwrap();
+
+        assert_eq!(find("format").attribute("frameDuration"), Some("1001/30000s"));
+        assert_eq!(find("format").attribute("name"), Some("FFVideoFormat1080p2997"));
+        // The sequence and gap run to the end of the last caption, past the video.
+        assert_eq!(find("sequence").attribute("duration"), Some("360360/30000s"));
+        assert_eq!(find("sequence").attribute("tcFormat"), Some("NDF"));
+        assert_eq!(find("gap").attribute("duration"), Some("360360/30000s"));
+
+        let written: Vec<_> = xml.descendants().filter(|n| n.has_tag_name("caption")).collect();
+        assert_eq!(written.len(), 3);
+        for node in &written {
+            assert_eq!(node.attribute("role"), Some("iTT?captionFormat=ITT.en"));
+        }
+        let timing: Vec<(i64, i64)> = written
+            .iter()
+            .map(|n| (n.attribute("offset").unwrap(), n.attribute("duration").unwrap()))
+            .map(|(offset, duration)| (frames(offset, frame_rate), frames(duration, frame_rate)))
+            .collect();
+        assert_eq!(timing, [(30, 45), (90, 30), (270, 90)]);
+        assert_eq!(written[0].attribute("offset"), Some("30030/30000s"));
+        assert_eq!(written[0].attribute("duration"), Some("45045/30000s"));
+
+        assert_eq!(written[0].attribute("name"), Some("First line"));
+        fn text<'a, 'input>(node: &roxmltree::Node<'a, 'input>) -> roxmltree::Node<'a, 'input> {
+            node.descendants().find(|d| d.has_tag_name("text")).unwrap()
+        }
+        assert_eq!(text(&written[0]).attribute("placement"), Some("bottom"));
+        assert_eq!(text(&written[1]).attribute("placement"), Some("top"));
+        let style = written[1].descendants().find(|d| d.has_tag_name("text-style") && d.has_attribute("font")).unwrap();
+        assert_eq!(style.attribute("italic"), Some("1"));
+        let content: String = text(&written[1]).descendants().filter(|d| d.is_text()).filter_map(|d| d.text()).collect();
+        assert_eq!(content, "Top & <tagged>");
+    }
+
+    #[test]
+    fn writes_whole_seconds_without_a_denominator() {
+        let frame_rate = FrameRate::from_fps(25.0, false).unwrap();
+        assert_eq!(rational_time(frame_rate, 0), "0s");
+        assert_eq!(rational_time(frame_rate, 50), "2s");
+        assert_eq!(rational_time(frame_rate, 3), "3/25s");
+    }
+}
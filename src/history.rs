@@ -0,0 +1,514 @@
+use crate::structs::{Caption, CaptionStyle, Word};
+use std::collections::VecDeque;
+
+// Number of undo steps kept.
+const MAX_STEPS: usize = 100;
+
+// A caption an edit touched, by its position in the caption list before and
+// after the edit. Captions that are not touched must keep their relative order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Touched {
+    Inserted(usize),       // position after
+    Removed(usize),        // position before
+    Changed(usize, usize), // positions before and after; they differ if it moved
+}
+
+// A caption field's value before or after an edit.
+#[derive(Debug, Clone)]
+enum Field {
+    StartMs(i32),
+    EndMs(i32),
+    Text(String),
+    Speaker(Option<String>),
+    Confidence(f32),
+    Style(Box<CaptionStyle>),
+    Words(Vec<Word>),
+    Track(Option<String>),
+}
+
+// One reversible change in a step. Remove and Move `from` positions are in the
+// list before the step, Insert and Move `to` positions in the list after it.
+// Modify positions are after the step.
+#[derive(Debug, Clone)]
+enum Change {
+    Insert { index: usize, caption: Caption },
+    Remove { index: usize, caption: Caption },
+    Move { from: usize, to: usize },
+    Modify { index: usize, before: Vec<Field>, after: Vec<Field> },
+}
+
+struct Entry {
+    label: String,
+    steps: Vec<Vec<Change>>,
+}
+
+struct Transaction {
+    label: String,
+    depth: usize,
+    steps: Vec<Vec<Change>>,
+}
+
+// Undo/redo history stored as patches between successive states. Each edit
+// reports the captions it touched, so recording, undoing and redoing it only
+// look at those. A copy of the last recorded state provides the values the
+// edited captions had before. Edits made inside a transaction are undone and
+// redone as a single step.
+pub struct History {
+    current: Vec<Caption>,
+    entries: VecDeque<Entry>,
+    applied: usize, // entries[..applied] are applied; the rest can be redone
+    transaction: Option<Transaction>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Labels<'a> {
+    pub undo: Vec<&'a str>, // most recent first
+    pub redo: Vec<&'a str>, // next first
+}
+
+impl History {
+    pub fn new(captions: &[Caption]) -> Self {
+        History {
+            current: captions.to_vec(),
+            entries: VecDeque::with_capacity(MAX_STEPS),
+            applied: 0,
+            transaction: None,
+        }
+    }
+
+    // Records an edit that left `captions` with the `touched` captions inserted,
+    // removed or changed. Does nothing when none of them changed.
+    pub fn record(&mut self, label: &str, captions: &[Caption], touched: &[Touched]) {
+        let changes = self.changes(captions, touched);
+        if changes.is_empty() {
+            return;
+        }
+        patch(&mut self.current, &changes, true);
+
+        // Patches are keyed by position, so an edit that under-reports what it
+        // touched would corrupt later undos. Debug builds catch any mismatch;
+        // release builds only notice a changed length and start over.
+        debug_assert_eq!(self.current, captions, "{} reported the wrong touched captions", label);
+        if self.current.len() != captions.len() {
+            log::warn!("History lost track of an edit to {} captions; clearing undo history", captions.len());
+            *self = History::new(captions);
+            return;
+        }
+
+        match &mut self.transaction {
+            Some(transaction) => transaction.steps.push(changes),
+            None => self.push(label.to_string(), vec![changes]),
+        }
+    }
+
+    // Starts grouping edits under `label`. Nested transactions join the outer one.
+    pub fn begin(&mut self, label: &str) {
+        match &mut self.transaction {
+            Some(transaction) => transaction.depth += 1,
+            None => {
+                self.transaction = Some(Transaction { label: label.to_string(), depth: 1, steps: Vec::new() })
+            }
+        }
+    }
+
+    // Ends a transaction; the outermost commit records everything as one step.
+    pub fn commit(&mut self) {
+        let Some(mut transaction) = self.transaction.take() else {
+            return;
+        };
+        if transaction.depth > 1 {
+            transaction.depth -= 1;
+            self.transaction = Some(transaction);
+        } else if !transaction.steps.is_empty() {
+            self.push(transaction.label, transaction.steps);
+        }
+    }
+
+    // Closes any open transaction, then reverts the last step in `captions`.
+    pub fn undo(&mut self, captions: &mut Vec<Caption>) -> bool {
+        self.flush();
+        if self.applied == 0 {
+            return false;
+        }
+        self.applied -= 1;
+        for changes in self.entries[self.applied].steps.iter().rev() {
+            patch(captions, changes, false);
+            patch(&mut self.current, changes, false);
+        }
+        true
+    }
+
+    pub fn redo(&mut self, captions: &mut Vec<Caption>) -> bool {
+        self.flush();
+        if self.applied >= self.entries.len() {
+            return false;
+        }
+        for changes in &self.entries[self.applied].steps {
+            patch(captions, changes, true);
+            patch(&mut self.current, changes, true);
+        }
+        self.applied += 1;
+        true
+    }
+
+    pub fn labels(&self) -> Labels<'_> {
+        Labels {
+            undo: self.entries.range(..self.applied).rev().map(|e| e.label.as_str()).collect(),
+            redo: self.entries.range(self.applied..).map(|e| e.label.as_str()).collect(),
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(transaction) = self.transaction.take()
+            && !transaction.steps.is_empty()
+        {
+            self.push(transaction.label, transaction.steps);
+        }
+    }
+
+    fn push(&mut self, label: String, steps: Vec<Vec<Change>>) {
+        self.entries.truncate(self.applied);
+        self.entries.push_back(Entry { label, steps });
+        self.applied += 1;
+
+        if self.entries.len() > MAX_STEPS {
+            self.entries.pop_front();
+            self.applied -= 1;
+        }
+    }
+
+    // The changes that turn the recorded state into `captions`, looking only at
+    // the touched captions.
+    fn changes(&self, captions: &[Caption], touched: &[Touched]) -> Vec<Change> {
+        // When captions come and go, a changed caption can keep its position and
+        // still move past others, so its position is always recorded.
+        let structural = touched.iter().any(|t| !matches!(t, Touched::Changed(from, to) if from == to));
+        let mut changes = Vec::new();
+        for &touch in touched {
+            match touch {
+                Touched::Inserted(index) => {
+                    if let Some(caption) = captions.get(index) {
+                        changes.push(Change::Insert { index, caption: caption.clone() });
+                    }
+                }
+                Touched::Removed(index) => {
+                    if let Some(caption) = self.current.get(index) {
+                        changes.push(Change::Remove { index, caption: caption.clone() });
+                    }
+                }
+                Touched::Changed(from, to) => {
+                    let (Some(old), Some(new)) = (self.current.get(from), captions.get(to)) else {
+                        continue;
+                    };
+                    if structural {
+                        changes.push(Change::Move { from, to });
+                    }
+                    if old != new {
+                        let (before, after) = field_changes(old, new);
+                        changes.push(Change::Modify { index: to, before, after });
+                    }
+                }
+            }
+        }
+        changes
+    }
+}
+
+fn field_changes(old: &Caption, new: &Caption) -> (Vec<Field>, Vec<Field>) {
+    let mut before = Vec::new();
+    let mut after = Vec::new();
+    macro_rules! compare {
+        ($field:ident, $variant:ident $(, $wrap:path)?) => {
+            if old.$field != new.$field {
+                before.push(Field::$variant($($wrap)?(old.$field.clone())));
+                after.push(Field::$variant($($wrap)?(new.$field.clone())));
+            }
+        };
+    }
+
+    compare!(start_ms, StartMs);
+    compare!(end_ms, EndMs);
+    compare!(text, Text);
+    compare!(speaker, Speaker);
+    compare!(confidence, Confidence);
+    compare!(style, Style, Box::new);
+    compare!(words, Words);
+    compare!(track, Track);
+
+    (before, after)
+}
+
+// Applies a step's changes (`forward`) or reverts them. Captions leaving the
+// list are taken out from the back and those entering are put in from the
+// front, so every position stays valid; untouched captions keep their order.
+fn patch(captions: &mut Vec<Caption>, changes: &[Change], forward: bool) {
+    if !forward {
+        set_all_fields(captions, changes, forward);
+    }
+
+    // (position taken out, where it goes back in if it is moving)
+    let mut out: Vec<(usize, Option<usize>)> = changes
+        .iter()
+        .filter_map(|change| match (change, forward) {
+            (Change::Remove { index, .. }, true) | (Change::Insert { index, .. }, false) => Some((*index, None)),
+            (Change::Move { from, to }, true) => Some((*from, Some(*to))),
+            (Change::Move { from, to }, false) => Some((*to, Some(*from))),
+            _ => None,
+        })
+        .collect();
+    out.sort_unstable_by_key(|&(index, _)| std::cmp::Reverse(index));
+
+    let mut put: Vec<(usize, Caption)> = Vec::new();
+    for (index, destination) in out {
+        let caption = captions.remove(index);
+        if let Some(destination) = destination {
+            put.push((destination, caption));
+        }
+    }
+    put.extend(changes.iter().filter_map(|change| match (change, forward) {
+        (Change::Insert { index, caption }, true) | (Change::Remove { index, caption }, false) => {
+            Some((*index, caption.clone()))
+        }
+        _ => None,
+    }));
+    put.sort_unstable_by_key(|(index, _)| *index);
+    for (index, caption) in put {
+        captions.insert(index, caption);
+    }
+
+    if forward {
+        set_all_fields(captions, changes, forward);
+    }
+}
+
+fn set_all_fields(captions: &mut [Caption], changes: &[Change], forward: bool) {
+    for change in changes {
+        if let Change::Modify { index, before, after } = change {
+            set_fields(&mut captions[*index], if forward { after } else { before });
+        }
+    }
+}
+
+fn set_fields(caption: &mut Caption, fields: &[Field]) {
+    for field in fields.iter().cloned() {
+        match field {
+            Field::StartMs(value) => caption.start_ms = value,
+            Field::EndMs(value) => caption.end_ms = value,
+            Field::Text(value) => caption.text = value,
+            Field::Speaker(value) => caption.speaker = value,
+            Field::Confidence(value) => caption.confidence = value,
+            Field::Style(value) => caption.style = *value,
+            Field::Words(value) => caption.words = value,
+            Field::Track(value) => caption.track = value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::caption;
+
+    fn captions(n: i32) -> Vec<Caption> {
+        (0..n).map(|i| caption(&format!("c{}", i), i * 1000, i * 1000 + 900, &format!("text {}", i))).collect()
+    }
+
+    // What an edit from `old` to `new` touched, matching captions by id.
+    fn touched(old: &[Caption], new: &[Caption]) -> Vec<Touched> {
+        let position = |list: &[Caption], id: &str| list.iter().position(|c| c.id == id);
+        let mut touched: Vec<Touched> =
+            (0..old.len()).filter(|&i| position(new, &old[i].id).is_none()).map(Touched::Removed).collect();
+        for (to, caption) in new.iter().enumerate() {
+            match position(old, &caption.id) {
+                None => touched.push(Touched::Inserted(to)),
+                Some(from) if from != to || old[from] != *caption => touched.push(Touched::Changed(from, to)),
+                Some(_) => {}
+            }
+        }
+        touched
+    }
+
+    // Records the edit from `before` to `after`, then checks that undo and redo
+    // move between the two.
+    fn round_trip(before: Vec<Caption>, after: Vec<Caption>, touched: &[Touched]) {
+        let mut history = History::new(&before);
+        let mut live = after.clone();
+        history.record("Edit", &live, touched);
+
+        assert!(history.undo(&mut live));
+        assert_eq!(live, before);
+        assert!(history.redo(&mut live));
+        assert_eq!(live, after);
+        assert!(history.undo(&mut live));
+        assert_eq!(live, before);
+    }
+
+    #[test]
+    fn round_trips_insert() {
+        let before = captions(3);
+        let mut after = before.clone();
+        after.insert(1, caption("new", 500, 900, "new"));
+        round_trip(before, after, &[Touched::Inserted(1)]);
+    }
+
+    #[test]
+    fn round_trips_remove() {
+        let before = captions(4);
+        let mut after = before.clone();
+        after.remove(3);
+        after.remove(0);
+        round_trip(before, after, &[Touched::Removed(0), Touched::Removed(3)]);
+    }
+
+    #[test]
+    fn round_trips_modify() {
+        let before = captions(3);
+        let mut after = before.clone();
+        after[1].text = String::from("changed");
+        after[1].style.bold = true;
+        after[2].end_ms += 50;
+        round_trip(before, after, &[Touched::Changed(1, 1), Touched::Changed(2, 2)]);
+    }
+
+    #[test]
+    fn round_trips_reorder() {
+        let before = captions(5);
+        let mut after = before.clone();
+        let moved = after.remove(0);
+        after.push(Caption { start_ms: 9000, end_ms: 9500, ..moved });
+        let moved = after.remove(2);
+        after.insert(0, moved);
+        // c3 moves to the front and c0 to the back; the rest keep their order.
+        round_trip(before, after, &[Touched::Changed(3, 0), Touched::Changed(0, 4)]);
+    }
+
+    #[test]
+    fn round_trips_reorder_mixed_with_insert_and_remove() {
+        // c1 is removed, c4 moves to the front with a new start, c2 changes in
+        // place and two captions are added.
+        let before = captions(6);
+        let mut after = before.clone();
+        let mut moved = after.remove(4);
+        moved.start_ms = -500;
+        after.remove(1);
+        after.insert(0, moved);
+        after[2].text = String::from("changed");
+        after.insert(3, caption("a", 2950, 2990, "a"));
+        after.push(caption("b", 7000, 7500, "b"));
+
+        let expected = touched(&before, &after);
+        round_trip(before, after, &expected);
+    }
+
+    #[test]
+    fn only_touched_captions_are_stored() {
+        let before = captions(1000);
+        let mut history = History::new(&before);
+        let mut live = before.clone();
+        live[999].start_ms = -1;
+        let moved = live.remove(999);
+        live.insert(0, moved);
+        history.record("Move", &live, &[Touched::Changed(999, 0)]);
+
+        let changes = &history.entries[0].steps[0];
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(changes[0], Change::Move { from: 999, to: 0 }));
+        assert!(matches!(&changes[1], Change::Modify { index: 0, before, .. } if before.len() == 1));
+    }
+
+    #[test]
+    fn transaction_undoes_as_one_step() {
+        let before = captions(3);
+        let mut history = History::new(&before);
+        let mut live = before.clone();
+
+        history.begin("Drag");
+        for end_ms in [950, 1000, 1100] {
+            live[0].end_ms = end_ms;
+            history.record("Change timing", &live, &[Touched::Changed(0, 0)]);
+        }
+        live.remove(2);
+        history.record("Delete captions", &live, &[Touched::Removed(2)]);
+        history.commit();
+        let after = live.clone();
+
+        assert_eq!(history.labels().undo, ["Drag"]);
+        assert!(history.undo(&mut live));
+        assert_eq!(live, before);
+        assert!(history.redo(&mut live));
+        assert_eq!(live, after);
+    }
+
+    #[test]
+    fn random_edits_undo_and_redo_to_snapshots() {
+        // A small linear congruential generator keeps the test deterministic.
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut random = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % n.max(1) as u64) as usize
+        };
+
+        let mut live = captions(20);
+        let mut history = History::new(&live);
+        let mut snapshots = vec![live.clone()];
+        let mut position = 0;
+        let mut next_id = 0;
+
+        for _ in 0..400 {
+            match random(6) {
+                0 if position > 0 => {
+                    assert!(history.undo(&mut live));
+                    position -= 1;
+                    assert_eq!(live, snapshots[position]);
+                }
+                1 if position + 1 < snapshots.len() => {
+                    assert!(history.redo(&mut live));
+                    position += 1;
+                    assert_eq!(live, snapshots[position]);
+                }
+                _ => {
+                    let before = live.clone();
+                    for _ in 0..1 + random(4) {
+                        match random(4) {
+                            0 => {
+                                next_id += 1;
+                                let index = random(live.len() + 1);
+                                live.insert(index, caption(&format!("n{}", next_id), index as i32, 0, "new"));
+                            }
+                            1 if !live.is_empty() => {
+                                live.remove(random(live.len()));
+                            }
+                            2 if !live.is_empty() => {
+                                let index = random(live.len());
+                                live[index].text.push('!');
+                                live[index].start_ms += 7;
+                            }
+                            _ if !live.is_empty() => {
+                                let moved = live.remove(random(live.len()));
+                                live.insert(random(live.len() + 1), moved);
+                            }
+                            _ => {}
+                        }
+                    }
+                    history.record("Edit", &live, &touched(&before, &live));
+                    if live != before {
+                        snapshots.truncate(position + 1);
+                        snapshots.push(live.clone());
+                        position += 1;
+                        // Only the last MAX_STEPS steps can be undone.
+                        if snapshots.len() > MAX_STEPS + 1 {
+                            snapshots.remove(0);
+                            position -= 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        while history.undo(&mut live) {
+            position -= 1;
+            assert_eq!(live, snapshots[position]);
+        }
+        assert_eq!(position, 0);
+    }
+}
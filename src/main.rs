@@ -1,6 +1,24 @@
 mod structs;
 mod captioneditor;
 mod waveform;
+mod ass;
+mod detect;
+mod ebu_stl;
+mod edl;
+mod encoding;
+mod fcpxml;
+mod history;
+mod opus;
+mod qc;
+mod reflow;
+mod scc;
+mod segment;
+mod srt;
+mod timecode;
+mod ttml;
+mod whisper;
+mod words;
+mod youtube;
 
 fn main() {
 
@@ -0,0 +1,148 @@
+use crate::structs::{Caption, CaptionStyle, Word};
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+// An Opus Clip JSON export. Opus Clip publishes no schema for it, so this one
+// is our own reading of the export (clips, segments, word timings and
+// keywords) and tests/fixtures/opus_clip.json is a synthetic sample of it, not
+// a real export. Each clip is a range of the source video; segment and word
+// times are in milliseconds from the start of their clip.
+#[derive(Deserialize)]
+struct Export {
+    clips: Vec<Clip>,
+}
+
+#[derive(Deserialize)]
+struct Clip {
+    start_ms: i32,
+    #[serde(default)]
+    highlight_color: Option<String>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    segments: Vec<Segment>,
+}
+
+#[derive(Deserialize)]
+struct Segment {
+    start_ms: i32,
+    end_ms: i32,
+    text: String,
+    #[serde(default)]
+    speaker: Option<String>,
+    #[serde(default)]
+    words: Vec<ExportWord>,
+}
+
+#[derive(Deserialize)]
+struct ExportWord {
+    text: String,
+    start_ms: i32,
+    end_ms: i32,
+    #[serde(default = "full_confidence")]
+    confidence: f32,
+    #[serde(default)]
+    highlight: bool,
+}
+
+fn full_confidence() -> f32 {
+    1.0
+}
+
+// Parses an Opus Clip JSON export into captions on the source video's timeline.
+// Segments with a highlighted word or a clip keyword are emphasised: bold, and in
+// the clip's highlight colour when it has one.
+// Returned captions have empty ids; the editor assigns them.
+pub fn parse(content: &str, base: &CaptionStyle) -> Result<Vec<Caption>, JsValue> {
+    let export: Export = serde_json::from_str(content)
+        .map_err(|e| JsValue::from_str(&format!("Invalid Opus Clip export: {}", e)))?;
+
+    let mut captions = Vec::new();
+
+    for clip in export.clips {
+        let keywords: Vec<String> = clip.keywords.iter().map(|k| normalize_word(k)).collect();
+
+        for segment in clip.segments {
+            let text = segment.text.trim().to_string();
+            if text.is_empty() {
+                continue;
+            }
+
+            let emphasized = segment.words.iter().any(|w| w.highlight)
+                || text.split_whitespace().any(|w| keywords.contains(&normalize_word(w)));
+
+            let words: Vec<Word> = segment
+                .words
+                .into_iter()
+                .map(|word| Word {
+                    text: word.text.trim().to_string(),
+                    start_ms: clip.start_ms + word.start_ms,
+                    end_ms: clip.start_ms + word.end_ms,
+                    confidence: word.confidence,
+                })
+                .collect();
+            let confidence = if words.is_empty() {
+                1.0
+            } else {
+                words.iter().map(|w| w.confidence).sum::<f32>() / words.len() as f32
+            };
+
+            let mut style = base.clone();
+            if emphasized {
+                style.bold = true;
+                if let Some(color) = &clip.highlight_color {
+                    style.color = color.clone();
+                }
+            }
+
+            captions.push(Caption {
+                id: String::new(),
+                start_ms: clip.start_ms + segment.start_ms,
+                end_ms: clip.start_ms + segment.end_ms,
+                text,
+                speaker: segment.speaker,
+                confidence,
+                style,
+                words,
+                track: None,
+            });
+        }
+    }
+
+    Ok(captions)
+}
+
+fn normalize_word(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captioneditor::CaptionEditor;
+
+    #[test]
+    fn parses_sample_export() {
+        let content = include_str!("../tests/fixtures/opus_clip.json");
+        let captions = parse(content, &CaptionEditor::default_style()).unwrap();
+
+        assert_eq!(captions.len(), 3);
+        let first = &captions[0];
+        assert_eq!((first.start_ms, first.end_ms), (61000, 63400));
+        assert_eq!(first.speaker.as_deref(), Some("Host"));
+        assert_eq!(first.words.len(), 5);
+        assert_eq!((first.words[4].start_ms, first.words[4].end_ms), (62200, 63400));
+        assert!(first.style.bold);
+        assert_eq!(first.style.color, "#FFD400FF");
+
+        assert!(!captions[1].style.bold);
+        assert_eq!((captions[2].start_ms, captions[2].end_ms), (305200, 308000));
+        assert!(captions[2].words.is_empty());
+        assert_eq!(captions[2].confidence, 1.0);
+    }
+
+    #[test]
+    fn detects_sample_export() {
+        let content = include_str!("../tests/fixtures/opus_clip.json");
+        assert_eq!(crate::detect::detect_format(content), Some("opus"));
+    }
+}
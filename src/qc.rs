@@ -0,0 +1,298 @@
+use crate::structs::{Caption, Conflict, QcIssue};
+use crate::timecode::FrameRate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+// Limits checked by `check`. A rule set to None is not checked. Durations are
+// in milliseconds; gaps and shot-change distances are in frames.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub max_cps: Option<f32>,
+    pub min_cps: Option<f32>,
+    pub max_chars_per_line: Option<usize>,
+    pub max_lines: Option<usize>,
+    pub min_duration_ms: Option<i32>,
+    pub max_duration_ms: Option<i32>,
+    pub min_gap_frames: Option<i64>,
+    // Captions should start and end on a shot change or at least this far from one.
+    pub shot_change_frames: Option<i64>,
+    pub allow_overlap: bool,
+}
+
+// Built-in profiles, following each broadcaster's published guidelines for
+// adult programmes in English. YouTube publishes no reading-speed or duration
+// limits, so its profile uses common web-video practice instead.
+pub fn netflix() -> Profile {
+    Profile {
+        name: String::from("netflix"),
+        max_cps: Some(20.0),
+        min_cps: None,
+        max_chars_per_line: Some(42),
+        max_lines: Some(2),
+        min_duration_ms: Some(833),
+        max_duration_ms: Some(7000),
+        min_gap_frames: Some(2),
+        shot_change_frames: Some(12),
+        allow_overlap: false,
+    }
+}
+
+fn bbc() -> Profile {
+    Profile {
+        name: String::from("bbc"),
+        max_cps: Some(17.0),
+        max_chars_per_line: Some(37),
+        min_duration_ms: Some(1000),
+        min_gap_frames: Some(1),
+        ..netflix()
+    }
+}
+
+fn dcmp() -> Profile {
+    Profile {
+        name: String::from("dcmp"),
+        max_cps: Some(15.0),
+        max_chars_per_line: Some(32),
+        min_duration_ms: Some(1500),
+        max_duration_ms: Some(6000),
+        min_gap_frames: Some(1),
+        shot_change_frames: None,
+        ..netflix()
+    }
+}
+
+fn youtube() -> Profile {
+    Profile {
+        name: String::from("youtube"),
+        max_cps: Some(21.0),
+        min_duration_ms: Some(700),
+        max_duration_ms: Some(10000),
+        min_gap_frames: None,
+        shot_change_frames: None,
+        ..netflix()
+    }
+}
+
+// Custom profiles start from the Netflix limits; fields left out keep them.
+impl Default for Profile {
+    fn default() -> Self {
+        Profile { name: String::from("custom"), ..netflix() }
+    }
+}
+
+impl Profile {
+    pub fn preset(name: &str) -> Result<Profile, JsValue> {
+        match name.to_ascii_lowercase().as_str() {
+            "netflix" => Ok(netflix()),
+            "bbc" => Ok(bbc()),
+            "dcmp" => Ok(dcmp()),
+            "youtube" => Ok(youtube()),
+            _ => Err(JsValue::from_str(&format!("Unknown QC profile: {}", name))),
+        }
+    }
+}
+
+// Checks captions against a profile. `shot_changes` are times in ms, sorted.
+// Issues come back in timeline order.
+pub fn check(captions: &[Caption], profile: &Profile, frame_rate: FrameRate, shot_changes: &[i32]) -> Vec<QcIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |caption: &Caption, rule: &str, severity: &str, value: f64, limit: f64| {
+        issues.push(QcIssue {
+            caption_id: caption.id.clone(),
+            rule: rule.to_string(),
+            severity: severity.to_string(),
+            value,
+            limit,
+            start_ms: caption.start_ms,
+        });
+    };
+
+    for caption in captions {
+        let duration_ms = caption.end_ms - caption.start_ms;
+        let lines: Vec<&str> = caption.text.lines().map(str::trim).collect();
+
+        if duration_ms > 0 {
+            let chars = lines.iter().map(|l| l.chars().count()).sum::<usize>() as f32;
+            let cps = chars * 1000.0 / duration_ms as f32;
+            let rounded = (cps as f64 * 10.0).round() / 10.0;
+            if let Some(max) = profile.max_cps
+                && cps > max
+            {
+                issue(caption, "max_cps", "error", rounded, max as f64);
+            }
+            if let Some(min) = profile.min_cps
+                && cps < min
+            {
+                issue(caption, "min_cps", "warning", rounded, min as f64);
+            }
+        }
+
+        if let Some(max) = profile.max_chars_per_line {
+            let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+            if longest > max {
+                issue(caption, "max_chars_per_line", "error", longest as f64, max as f64);
+            }
+        }
+        if let Some(max) = profile.max_lines
+            && lines.len() > max
+        {
+            issue(caption, "max_lines", "error", lines.len() as f64, max as f64);
+        }
+        if let Some(min) = profile.min_duration_ms
+            && duration_ms < min
+        {
+            issue(caption, "min_duration", "error", duration_ms as f64, min as f64);
+        }
+        if let Some(max) = profile.max_duration_ms
+            && duration_ms > max
+        {
+            issue(caption, "max_duration", "warning", duration_ms as f64, max as f64);
+        }
+
+        if let Some(zone) = profile.shot_change_frames {
+            for (rule, time) in [("shot_change_start", caption.start_ms), ("shot_change_end", caption.end_ms)] {
+                let distance = nearest_distance(shot_changes, time).map(|ms| frame_rate.ms_to_frames(ms));
+                if let Some(frames) = distance
+                    && frames > 0
+                    && frames < zone
+                {
+                    issue(caption, rule, "warning", frames as f64, zone as f64);
+                }
+            }
+        }
+    }
+
+    // Gaps and overlaps are between neighbours on the same track.
+    let order = timeline_order(captions);
+    if let Some(min_gap) = profile.min_gap_frames {
+        for pair in order.windows(2) {
+            let (earlier, later) = (&captions[pair[0]], &captions[pair[1]]);
+            let gap = later.start_ms - earlier.end_ms;
+            if earlier.track != later.track || gap < 0 {
+                continue;
+            }
+            let frames = frame_rate.ms_to_frames(gap);
+            if frames < min_gap {
+                issue(later, "min_gap", "warning", frames as f64, min_gap as f64);
+            }
+        }
+    }
+    if !profile.allow_overlap {
+        let by_id: HashMap<&str, &Caption> = captions.iter().map(|c| (c.id.as_str(), c)).collect();
+        for conflict in find_overlaps(captions) {
+            if let Some(later) = by_id.get(conflict.second_id.as_str()) {
+                issue(later, "overlap", "error", conflict.overlap_ms as f64, 0.0);
+            }
+        }
+    }
+
+    issues.sort_by_key(|i| i.start_ms);
+    issues
+}
+
+// Distance in ms from `time` to the nearest of the sorted `times`.
+fn nearest_distance(times: &[i32], time: i32) -> Option<i32> {
+    let index = times.partition_point(|&t| t < time);
+    [index.checked_sub(1), Some(index)]
+        .into_iter()
+        .flatten()
+        .filter_map(|i| times.get(i))
+        .map(|&t| (t - time).abs())
+        .min()
+}
+
+// Finds overlapping pairs with a sweep over captions sorted by track and
+// start time, keeping only the captions still running at each start.
+pub fn find_overlaps(captions: &[Caption]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+
+    for i in timeline_order(captions) {
+        let caption = &captions[i];
+        active.retain(|&a| captions[a].track == caption.track && captions[a].end_ms > caption.start_ms);
+        for &a in &active {
+            let other = &captions[a];
+            conflicts.push(Conflict {
+                first_id: other.id.clone(),
+                second_id: caption.id.clone(),
+                overlap_ms: other.end_ms.min(caption.end_ms) - caption.start_ms,
+            });
+        }
+        if caption.end_ms > caption.start_ms {
+            active.push(i);
+        }
+    }
+
+    conflicts
+}
+
+// Caption positions ordered by track, then start and end time.
+pub fn timeline_order(captions: &[Caption]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..captions.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&captions[a], &captions[b]);
+        (&a.track, a.start_ms, a.end_ms).cmp(&(&b.track, b.start_ms, b.end_ms))
+    });
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::caption;
+
+    fn rules(issues: &[QcIssue]) -> Vec<(&str, &str)> {
+        issues.iter().map(|i| (i.caption_id.as_str(), i.rule.as_str())).collect()
+    }
+
+    #[test]
+    fn reports_each_limit_in_timeline_order() {
+        let captions = vec![
+            caption("late", 20_000, 20_500, "Short."),
+            caption("fast", 0, 1000, "This line has far too many characters for a single second"),
+            caption("long", 2000, 10_000, "One\ntwo\nthree"),
+            caption("close", 10_020, 12_000, "Right after."),
+        ];
+        let issues = check(&captions, &netflix(), FrameRate::default(), &[]);
+
+        assert_eq!(
+            rules(&issues),
+            [
+                ("fast", "max_cps"),
+                ("fast", "max_chars_per_line"),
+                ("long", "max_lines"),
+                ("long", "max_duration"),
+                ("close", "min_gap"),
+                ("late", "min_duration"),
+            ]
+        );
+        assert_eq!((issues[0].value, issues[0].limit), (57.0, 20.0));
+        assert_eq!((issues[4].value, issues[4].limit), (1.0, 2.0));
+    }
+
+    #[test]
+    fn reports_overlaps_only_within_a_track() {
+        let mut captions = vec![caption("a", 0, 2000, "First."), caption("b", 1500, 3000, "Second.")];
+        let issues = check(&captions, &netflix(), FrameRate::default(), &[]);
+        assert_eq!(rules(&issues), [("b", "overlap")]);
+        assert_eq!(issues[0].value, 500.0);
+
+        captions[1].track = Some(String::from("speaker2"));
+        assert!(check(&captions, &netflix(), FrameRate::default(), &[]).is_empty());
+    }
+
+    #[test]
+    fn shot_change_rule_names_the_edge() {
+        // At 30 fps the Netflix zone of 12 frames is 400 ms.
+        let captions = vec![caption("a", 1100, 2900, "Near two shot changes.")];
+        let issues = check(&captions, &netflix(), FrameRate::default(), &[1000, 3000]);
+        assert_eq!(rules(&issues), [("a", "shot_change_start"), ("a", "shot_change_end")]);
+        assert_eq!(issues[0].value, 3.0);
+
+        // On the shot change itself is fine.
+        assert!(check(&captions, &netflix(), FrameRate::default(), &[1100, 2900]).is_empty());
+    }
+}
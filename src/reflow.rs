@@ -0,0 +1,207 @@
+use crate::structs::CaptionStyle;
+use crate::words::{self, BreakWeights};
+use web_sys::CanvasRenderingContext2d;
+
+// Text used to measure a typical character width in the caption's font.
+const SAMPLE: &str = "the quick brown fox jumps over the lazy dog";
+
+// Measures line widths in characters or, with a canvas context, in pixels
+// using the caption's font and letter spacing.
+pub enum Metrics<'a> {
+    Characters,
+    Canvas { context: &'a CanvasRenderingContext2d, letter_spacing: f32 },
+}
+
+impl<'a> Metrics<'a> {
+    pub fn canvas(context: &'a CanvasRenderingContext2d, style: &CaptionStyle) -> Self {
+        let weight = if style.bold { "bold " } else { "" };
+        let slant = if style.italic { "italic " } else { "" };
+        context.set_font(&format!("{}{}{}px {}", slant, weight, style.font_size, style.font_family));
+        Metrics::Canvas { context, letter_spacing: style.letter_spacing }
+    }
+
+    pub fn width(&self, line: &str) -> f32 {
+        let chars = line.chars().count() as f32;
+        match self {
+            Metrics::Characters => chars,
+            Metrics::Canvas { context, letter_spacing } => match context.measure_text(line) {
+                Ok(metrics) => metrics.width() as f32 + letter_spacing * (chars - 1.0).max(0.0),
+                Err(_) => chars,
+            },
+        }
+    }
+
+    // The width of `max_chars` typical characters.
+    pub fn limit(&self, max_chars: usize) -> f32 {
+        match self {
+            Metrics::Characters => max_chars as f32,
+            Metrics::Canvas { .. } => {
+                let typical = self.width(SAMPLE) / SAMPLE.chars().count() as f32;
+                typical * max_chars as f32
+            }
+        }
+    }
+}
+
+// Breaks text into at most `max_lines` lines of at most `max_chars` characters,
+// using as few lines as possible and keeping them balanced. With a canvas the
+// lines must also fit the measured width of `max_chars` typical characters.
+// Lines that open with a dash or a speaker label keep the break before them.
+// Breaks are otherwise preferred after punctuation and before clause words, and
+// avoided after articles. Returns None when the text cannot fit.
+pub fn reflow(text: &str, max_lines: usize, max_chars: usize, metrics: &Metrics) -> Option<String> {
+    let limit = metrics.limit(max_chars);
+    let fits = |line: &str| line.chars().count() <= max_chars && metrics.width(line) <= limit;
+
+    // hard[i]: words[i] starts a line of its own.
+    let mut words: Vec<&str> = Vec::new();
+    let mut hard: Vec<bool> = Vec::new();
+    for line in text.lines() {
+        let opens = !words.is_empty() && starts_turn(line);
+        for (k, word) in line.split_whitespace().enumerate() {
+            words.push(word);
+            hard.push(k == 0 && opens);
+        }
+    }
+    let single = words.join(" ");
+    if words.is_empty() || (!hard.contains(&true) && fits(&single)) {
+        return Some(single);
+    }
+
+    // widths[i][j - i - 1] is the width of words[i..j] on one line, while it
+    // fits and does not run over a hard break.
+    let widths: Vec<Vec<f32>> = (0..words.len())
+        .map(|i| {
+            (i + 1..=words.len())
+                .take_while(|&j| j == i + 1 || !hard[j - 1])
+                .map(|j| words[i..j].join(" "))
+                .take_while(|line| fits(line))
+                .map(|line| metrics.width(&line))
+                .collect()
+        })
+        .collect();
+
+    (2..=max_lines).find_map(|lines| best_breaks(&words, &widths, lines, limit)).map(|breaks| {
+        let mut start = 0;
+        breaks
+            .into_iter()
+            .chain(std::iter::once(words.len()))
+            .map(|end| {
+                let line = words[start..end].join(" ");
+                start = end;
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+// True when a line opens a new speaker's turn: a leading dash, a bracketed
+// speaker such as "[Anna]", or an upper-case label such as "ANNA:".
+fn starts_turn(line: &str) -> bool {
+    let line = line.trim_start();
+    if line.starts_with(['-', '\u{2013}', '\u{2014}', '[']) {
+        return true;
+    }
+    line.split_once(':').is_some_and(|(label, _)| {
+        label.split_whitespace().count() <= 3
+            && label.chars().any(char::is_alphabetic)
+            && !label.chars().any(char::is_lowercase)
+    })
+}
+
+// Splits words into exactly `lines` lines, minimising the sum of squared line
+// widths (which favours balanced lines) plus the cost of each break.
+// Returns the word index each line after the first starts at.
+fn best_breaks(words: &[&str], widths: &[Vec<f32>], lines: usize, limit: f32) -> Option<Vec<usize>> {
+    let n = words.len();
+    let width = |i: usize, j: usize| widths[i].get(j - i - 1).copied();
+
+    // cost[l][j]: the best cost of setting words[..j] on l + 1 lines.
+    let mut cost = vec![vec![f32::INFINITY; n + 1]; lines];
+    let mut from = vec![vec![0; n + 1]; lines];
+    for (j, w) in widths[0].iter().enumerate() {
+        cost[0][j + 1] = w * w;
+    }
+    for l in 1..lines {
+        for j in l + 1..=n {
+            for i in l..j {
+                let (Some(w), previous) = (width(i, j), cost[l - 1][i]) else {
+                    continue;
+                };
+                let total = previous + w * w + break_cost(words[i - 1], words[i], limit);
+                if total < cost[l][j] {
+                    cost[l][j] = total;
+                    from[l][j] = i;
+                }
+            }
+        }
+    }
+
+    if !cost[lines - 1][n].is_finite() {
+        return None;
+    }
+    let mut breaks = Vec::with_capacity(lines - 1);
+    let mut j = n;
+    for l in (1..lines).rev() {
+        j = from[l][j];
+        breaks.push(j);
+    }
+    breaks.reverse();
+    Some(breaks)
+}
+
+// Break weights as fractions of the squared line limit.
+const WEIGHTS: BreakWeights = BreakWeights { sentence_end: 0.15, clause_end: 0.1, clause_word: 0.05, binding: 0.5 };
+
+// The cost of breaking between `before` and `after`, relative to the line limit.
+fn break_cost(before: &str, after: &str, limit: f32) -> f32 {
+    words::break_cost(before, after, &WEIGHTS) * limit * limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reflow_chars(text: &str, max_lines: usize, max_chars: usize) -> Option<String> {
+        reflow(text, max_lines, max_chars, &Metrics::Characters)
+    }
+
+    #[test]
+    fn keeps_short_text_on_one_line() {
+        assert_eq!(reflow_chars("Just a\nshort line.", 2, 42).as_deref(), Some("Just a short line."));
+    }
+
+    #[test]
+    fn balances_lines_within_the_character_limit() {
+        let text = "We went down to the river and watched the boats go by";
+        assert_eq!(reflow_chars(text, 2, 32).as_deref(), Some("We went down to the river\nand watched the boats go by"));
+        assert_eq!(reflow_chars(text, 2, 22), None);
+        assert_eq!(reflow_chars(text, 3, 22).as_deref(), Some("We went down to\nthe river and watched\nthe boats go by"));
+    }
+
+    #[test]
+    fn prefers_breaking_after_punctuation() {
+        let text = "It was late, so we all went home early";
+        assert_eq!(reflow_chars(text, 2, 30).as_deref(), Some("It was late,\nso we all went home early"));
+    }
+
+    #[test]
+    fn keeps_dialogue_and_speaker_lines_apart() {
+        assert_eq!(reflow_chars("- Are you coming?\n- Yes.", 2, 42).as_deref(), Some("- Are you coming?\n- Yes."));
+        assert_eq!(
+            reflow_chars("ANNA: Where were you\nall night?\nBEN: Out.", 2, 42).as_deref(),
+            Some("ANNA: Where were you all night?\nBEN: Out.")
+        );
+        assert_eq!(reflow_chars("- One.\n- Two.\n- Three.", 2, 42), None);
+    }
+
+    #[test]
+    fn speaker_labels_are_upper_case_or_bracketed() {
+        assert!(starts_turn("DR SMITH: Hello"));
+        assert!(starts_turn("[Anna] Hello"));
+        assert!(starts_turn("\u{2014} Hello"));
+        assert!(!starts_turn("Note: this is one sentence"));
+        assert!(!starts_turn("at 10:30 we left"));
+    }
+}
@@ -0,0 +1,664 @@
+use crate::structs::{Caption, CaptionStyle, Position, TextAlign};
+use crate::timecode::FrameRate;
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+// SCC timecodes always run at 29.97 drop-frame; one byte pair is sent per frame.
+const FRAME_RATE: FrameRate = FrameRate { numerator: 30000, denominator: 1001, drop_frame: true };
+const COLUMNS: usize = 32;
+const ROWS: usize = 15;
+
+// Miscellaneous control codes on data channel 1.
+const RCL: [u8; 2] = [0x14, 0x20]; // resume caption loading (pop-on)
+const BS: [u8; 2] = [0x14, 0x21]; // backspace
+const DER: [u8; 2] = [0x14, 0x24]; // delete to end of row
+const RDC: [u8; 2] = [0x14, 0x29]; // resume direct captioning (paint-on)
+const EDM: [u8; 2] = [0x14, 0x2C]; // erase displayed memory
+const CR: [u8; 2] = [0x14, 0x2D]; // carriage return (roll-up)
+const ENM: [u8; 2] = [0x14, 0x2E]; // erase non-displayed memory
+const EOC: [u8; 2] = [0x14, 0x2F]; // end of caption (swap memories)
+
+// Preamble address code first byte and second-byte base for rows 1-15.
+const PAC_ROWS: [(u8, u8); ROWS] = [
+    (0x11, 0x40), (0x11, 0x60), (0x12, 0x40), (0x12, 0x60), (0x15, 0x40),
+    (0x15, 0x60), (0x16, 0x40), (0x16, 0x60), (0x17, 0x40), (0x17, 0x60),
+    (0x10, 0x40), (0x13, 0x40), (0x13, 0x60), (0x14, 0x40), (0x14, 0x60),
+];
+
+// Positions in the basic character set that differ from ASCII.
+const BASIC_OVERRIDES: [(u8, char); 10] = [
+    (0x2A, 'á'), (0x5C, 'é'), (0x5E, 'í'), (0x5F, 'ó'), (0x60, 'ú'),
+    (0x7B, 'ç'), (0x7C, '÷'), (0x7D, 'Ñ'), (0x7E, 'ñ'), (0x7F, '█'),
+];
+
+// Special characters, sent as 0x11 0x30-0x3F.
+const SPECIAL: [char; 16] = [
+    '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', '\u{a0}', 'è', 'â', 'ê', 'î', 'ô', 'û',
+];
+
+// Extended characters, sent as 0x12 or 0x13 followed by 0x20-0x3F. Each replaces
+// the basic character sent just before it.
+const EXTENDED_12: [char; 32] = [
+    'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '’', '—', '©', '℠', '•', '“', '”',
+    'À', 'Â', 'Ç', 'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+];
+const EXTENDED_13: [char; 32] = [
+    'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~',
+    'Ä', 'ä', 'Ö', 'ö', 'ß', '¥', '¤', '¦', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+];
+
+// Foreground colours selectable by mid-row codes, in code order.
+const COLORS: [&str; 7] = ["#FFFFFF", "#00FF00", "#0000FF", "#00FFFF", "#FF0000", "#FFFF00", "#FF00FF"];
+
+enum Token {
+    Char(u8),
+    Code([u8; 2]), // control codes are sent twice, on a byte-pair boundary
+}
+
+// Encodes captions as pop-on CEA-608 data on channel 1. Each caption is loaded
+// into non-displayed memory ahead of time so that its EOC lands on the start frame.
+pub fn write(captions: &[Caption]) -> String {
+    // (desired frame, byte pairs)
+    let mut events: Vec<(i64, Vec<[u8; 2]>)> = Vec::new();
+    let mut previous_clear: Option<i64> = None;
+    let mut previous_display_end = 0;
+
+    for (i, caption) in captions.iter().enumerate() {
+        let start = FRAME_RATE.ms_to_frames(caption.start_ms.max(0));
+        let end = FRAME_RATE.ms_to_frames(caption.end_ms).max(start + 1);
+
+        let load = to_words(&load_tokens(caption));
+        let mut load_at = start - load.len() as i64 - 2;
+        // Finish loading before the previous caption is cleared rather than delaying the clear.
+        if let Some(clear) = previous_clear
+            && load_at < clear + 2
+            && load_at + load.len() as i64 > clear
+            && clear - load.len() as i64 >= previous_display_end
+        {
+            load_at = clear - load.len() as i64;
+        }
+
+        events.push((load_at, load));
+        events.push((start, vec![EOC, EOC]));
+        previous_display_end = start + 2;
+
+        // The next caption's EOC replaces this one, so only clear when there is a gap.
+        let next_start = captions.get(i + 1).map(|c| FRAME_RATE.ms_to_frames(c.start_ms));
+        previous_clear = if next_start.is_some_and(|next| next <= end) {
+            None
+        } else {
+            events.push((end, vec![EDM, EDM]));
+            Some(end)
+        };
+    }
+
+    events.sort_by_key(|(frame, _)| *frame);
+
+    let mut output = String::from("Scenarist_SCC V1.0\n\n");
+    let mut cursor = 0;
+    for (frame, words) in events {
+        let frame = frame.max(cursor);
+        cursor = frame + words.len() as i64;
+
+        let hex: Vec<String> = words
+            .iter()
+            .map(|[a, b]| format!("{:02x}{:02x}", with_parity(*a), with_parity(*b)))
+            .collect();
+        output.push_str(&format!("{}\t{}\n\n", FRAME_RATE.frames_to_timecode(frame), hex.join(" ")));
+    }
+
+    output
+}
+
+fn load_tokens(caption: &Caption) -> Vec<Token> {
+    let style = &caption.style;
+
+    // A mid-row code switches to italics or a colour, and takes up a column itself.
+    let color_index = COLORS.iter().position(|c| style.color.to_uppercase().starts_with(c)).unwrap_or(0);
+    let underline = style.underline as u8;
+    let mid_row = if style.italic {
+        Some([0x11, 0x2E + underline])
+    } else if color_index > 0 {
+        Some([0x11, 0x20 + color_index as u8 * 2 + underline])
+    } else {
+        None
+    };
+    let lead = mid_row.is_some() as usize;
+
+    let lines = wrap(&caption.text, COLUMNS - lead);
+    let line_count = lines.len().clamp(1, ROWS);
+
+    let first_row = match style.position {
+        Position::Top => 1,
+        Position::Middle => 8 - line_count / 2,
+        Position::Bottom => ROWS + 1 - line_count,
+        Position::Custom(_, y) => ((y.clamp(0, 100) as usize * ROWS + 50) / 100).saturating_sub(line_count / 2),
+    }
+    .clamp(1, ROWS + 1 - line_count);
+
+    let center = match style.position {
+        Position::Custom(x, _) => x.clamp(0, 100) as usize * COLUMNS / 100,
+        _ => COLUMNS / 2,
+    };
+
+    let block_width = lines.iter().map(|l| l.chars().count() + lead).max().unwrap_or(0);
+    let block_left = center.saturating_sub(block_width / 2);
+
+    let mut tokens = vec![Token::Code(RCL), Token::Code(ENM)];
+    for (i, line) in lines.iter().take(ROWS).enumerate() {
+        let width = line.chars().count() + lead;
+        let column = match style.alignment {
+            TextAlign::Left => block_left,
+            TextAlign::Center => center.saturating_sub(width / 2),
+            TextAlign::Right => block_left + block_width - width,
+        }
+        .min(COLUMNS - width.min(COLUMNS));
+
+        // PACs indent in steps of four columns; tab offsets cover the remainder.
+        let (pac_first, pac_base) = PAC_ROWS[first_row + i - 1];
+        tokens.push(Token::Code([pac_first, pac_base + 0x10 + (column / 4) as u8 * 2 + underline]));
+        if column % 4 > 0 {
+            tokens.push(Token::Code([0x17, 0x20 + (column % 4) as u8]));
+        }
+        if let Some(code) = mid_row {
+            tokens.push(Token::Code(code));
+        }
+        for c in line.chars() {
+            tokens.extend(encode_char(c));
+        }
+    }
+
+    tokens
+}
+
+// Word-wraps text to `width` columns of the caption grid, keeping explicit line breaks.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            // Words longer than a row are hard-broken.
+            while word.len() > width {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                lines.push(word.drain(..width).collect());
+            }
+            let needed = if line.is_empty() { word.len() } else { line.chars().count() + 1 + word.len() };
+            if needed > width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.extend(word);
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    if lines.len() > 4 {
+        log::warn!("Caption uses {} rows; CEA-608 pop-on captions should not exceed 4", lines.len());
+    }
+    lines
+}
+
+fn encode_char(c: char) -> Vec<Token> {
+    // Prefer the basic set's straight quotes over the extended curly ones.
+    let c = match c {
+        '‘' | '’' => '\'',
+        '“' | '”' => '"',
+        _ => c,
+    };
+
+    if let Some((code, _)) = BASIC_OVERRIDES.iter().find(|(_, ch)| *ch == c) {
+        return vec![Token::Char(*code)];
+    }
+    if c.is_ascii() && (' '..='~').contains(&c) && !BASIC_OVERRIDES.iter().any(|(code, _)| *code == c as u8) {
+        return vec![Token::Char(c as u8)];
+    }
+    if let Some(i) = SPECIAL.iter().position(|ch| *ch == c) {
+        return vec![Token::Code([0x11, 0x30 + i as u8])];
+    }
+    for (first, table) in [(0x12, &EXTENDED_12), (0x13, &EXTENDED_13)] {
+        if let Some(i) = table.iter().position(|ch| *ch == c) {
+            return vec![Token::Char(fallback(c)), Token::Code([first, 0x20 + i as u8])];
+        }
+    }
+
+    log::warn!("Character {:?} cannot be encoded in CEA-608 and was dropped", c);
+    Vec::new()
+}
+
+// The basic character shown by decoders that do not support an extended character.
+fn fallback(c: char) -> u8 {
+    match c {
+        'Á' | 'À' | 'Â' | 'Ã' | 'Ä' | 'Å' => b'A',
+        'É' | 'È' | 'Ê' | 'Ë' => b'E',
+        'Í' | 'Ì' | 'Î' | 'Ï' => b'I',
+        'Ó' | 'Ò' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => b'O',
+        'Ú' | 'Ù' | 'Û' | 'Ü' => b'U',
+        'Ç' => b'C',
+        'ã' | 'ä' | 'å' => b'a',
+        'ë' => b'e',
+        'ì' | 'ï' => b'i',
+        'ò' | 'õ' | 'ö' | 'ø' => b'o',
+        'ù' | 'ü' => b'u',
+        'ß' => b's',
+        '«' | '»' => b'"',
+        '—' => b'-',
+        '¡' => b'!',
+        _ => b' ',
+    }
+}
+
+fn to_words(tokens: &[Token]) -> Vec<[u8; 2]> {
+    let mut words = Vec::new();
+    let mut pending: Option<u8> = None;
+    for token in tokens {
+        match token {
+            Token::Char(byte) => match pending.take() {
+                Some(first) => words.push([first, *byte]),
+                None => pending = Some(*byte),
+            },
+            Token::Code(code) => {
+                if let Some(first) = pending.take() {
+                    words.push([first, 0x00]);
+                }
+                words.push(*code);
+                words.push(*code);
+            }
+        }
+    }
+    if let Some(first) = pending {
+        words.push([first, 0x00]);
+    }
+    words
+}
+
+// CEA-608 bytes carry odd parity in the high bit.
+fn with_parity(byte: u8) -> u8 {
+    let byte = byte & 0x7F;
+    if byte.count_ones().is_multiple_of(2) { byte | 0x80 } else { byte }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    PopOn,
+    PaintOn,
+    RollUp(usize),
+}
+
+#[derive(Clone, Copy, Default)]
+struct Cell {
+    ch: char,
+    italic: bool,
+    underline: bool,
+    color: usize,
+}
+
+type Memory = BTreeMap<usize, BTreeMap<usize, Cell>>; // row -> column -> cell
+
+struct Decoder {
+    mode: Mode,
+    displayed: Memory,
+    non_displayed: Memory,
+    row: usize,
+    column: usize,
+    italic: bool,
+    underline: bool,
+    color: usize,
+    open: Option<(i64, Memory)>,
+    cues: Vec<(i64, i64, Memory)>,
+}
+
+// Decodes channel 1 of an SCC file (pop-on, paint-on and roll-up) into captions.
+// Returned captions have empty ids; the editor assigns them.
+pub fn parse(content: &str, base: &CaptionStyle) -> Result<Vec<Caption>, JsValue> {
+    let mut lines = content.trim_start_matches('\u{feff}').lines().map(str::trim).filter(|l| !l.is_empty());
+    if !lines.next().is_some_and(|header| header.starts_with("Scenarist_SCC")) {
+        return Err(JsValue::from_str("Invalid SCC file: missing Scenarist_SCC header"));
+    }
+
+    let mut decoder = Decoder {
+        mode: Mode::PopOn,
+        displayed: Memory::new(),
+        non_displayed: Memory::new(),
+        row: ROWS,
+        column: 0,
+        italic: false,
+        underline: false,
+        color: 0,
+        open: None,
+        cues: Vec::new(),
+    };
+    let mut last_frame = 0;
+
+    for line in lines {
+        let Some((timecode, data)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let frame_rate = FrameRate { drop_frame: timecode.contains(';'), ..FRAME_RATE };
+        let start = frame_rate
+            .timecode_to_frames(timecode)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid SCC timecode: {}", timecode)))?;
+
+        let mut previous_code: Option<[u8; 2]> = None;
+        let mut frame = start;
+        for word in data.split_whitespace() {
+            let value = u16::from_str_radix(word, 16)
+                .map_err(|_| JsValue::from_str(&format!("Invalid SCC byte pair: {}", word)))?;
+            let pair = [((value >> 8) as u8) & 0x7F, (value as u8) & 0x7F];
+
+            if (0x10..=0x1F).contains(&pair[0]) {
+                // Control codes are transmitted twice; act on the first only.
+                if previous_code == Some(pair) {
+                    previous_code = None;
+                } else {
+                    previous_code = Some(pair);
+                    decoder.control(pair, frame);
+                }
+            } else {
+                previous_code = None;
+                for byte in pair {
+                    if byte >= 0x20 {
+                        decoder.write(basic_char(byte));
+                    }
+                }
+            }
+            frame += 1;
+        }
+
+        // Paint-on and roll-up captions appear as they are written.
+        if decoder.mode != Mode::PopOn {
+            decoder.refresh(frame);
+        }
+        last_frame = last_frame.max(frame);
+    }
+
+    // A caption still on screen at the end of the file runs for a further two seconds.
+    decoder.refresh_to(last_frame + 60, Memory::new());
+
+    Ok(decoder
+        .cues
+        .into_iter()
+        .filter_map(|(start, end, memory)| caption_from_memory(start, end, &memory, base))
+        .collect())
+}
+
+impl Decoder {
+    fn memory(&mut self) -> &mut Memory {
+        if self.mode == Mode::PopOn { &mut self.non_displayed } else { &mut self.displayed }
+    }
+
+    fn write(&mut self, ch: char) {
+        let cell = Cell { ch, italic: self.italic, underline: self.underline, color: self.color };
+        let (row, column) = (self.row, self.column);
+        self.memory().entry(row).or_default().insert(column, cell);
+        self.column = (self.column + 1).min(COLUMNS - 1);
+    }
+
+    fn backspace(&mut self) {
+        if self.column > 0 {
+            self.column -= 1;
+            let (row, column) = (self.row, self.column);
+            if let Some(cells) = self.memory().get_mut(&row) {
+                cells.remove(&column);
+            }
+        }
+    }
+
+    fn control(&mut self, pair: [u8; 2], frame: i64) {
+        let [first, second] = pair;
+        match pair {
+            RCL => self.mode = Mode::PopOn,
+            RDC => self.mode = Mode::PaintOn,
+            [0x14, 0x25..=0x27] => {
+                self.mode = Mode::RollUp((second - 0x23) as usize);
+                self.non_displayed.clear();
+            }
+            BS => self.backspace(),
+            DER => {
+                let (row, column) = (self.row, self.column);
+                if let Some(cells) = self.memory().get_mut(&row) {
+                    cells.retain(|c, _| *c < column);
+                }
+            }
+            EDM => {
+                self.displayed.clear();
+                self.refresh(frame);
+            }
+            ENM => self.non_displayed.clear(),
+            EOC => {
+                std::mem::swap(&mut self.displayed, &mut self.non_displayed);
+                self.refresh(frame);
+            }
+            CR => {
+                if let Mode::RollUp(depth) = self.mode {
+                    // Roll the window up one row, dropping rows that scroll out of it.
+                    let base_row = self.row;
+                    let rolled: Memory = std::mem::take(&mut self.displayed)
+                        .into_iter()
+                        .filter(|(row, _)| *row + depth > base_row && *row > 1)
+                        .map(|(row, cells)| (row - 1, cells))
+                        .collect();
+                    self.displayed = rolled;
+                    self.refresh(frame);
+                }
+                self.column = 0;
+            }
+            [0x17, 0x21..=0x23] => self.column = (self.column + (second - 0x20) as usize).min(COLUMNS - 1),
+            [0x11, 0x20..=0x2F] => {
+                // Mid-row codes set attributes and display as a space.
+                let attribute = (second - 0x20) / 2;
+                self.underline = second & 1 == 1;
+                if attribute == 7 {
+                    self.italic = true;
+                } else {
+                    self.italic = false;
+                    self.color = attribute as usize;
+                }
+                self.write(' ');
+            }
+            [0x11, 0x30..=0x3F] => self.write(SPECIAL[(second - 0x30) as usize]),
+            [0x12, 0x20..=0x3F] => {
+                self.backspace();
+                self.write(EXTENDED_12[(second - 0x20) as usize]);
+            }
+            [0x13, 0x20..=0x3F] => {
+                self.backspace();
+                self.write(EXTENDED_13[(second - 0x20) as usize]);
+            }
+            [0x10..=0x17, 0x40..=0x7F] => {
+                let base = second & 0x60;
+                let Some(row) = PAC_ROWS.iter().position(|(f, b)| *f == first && *b == base) else {
+                    return;
+                };
+                let attribute = second & 0x1F;
+                self.underline = attribute & 1 == 1;
+                if attribute >= 0x10 {
+                    self.column = ((attribute - 0x10) / 2) as usize * 4;
+                    self.italic = false;
+                    self.color = 0;
+                } else {
+                    self.column = 0;
+                    self.italic = attribute >= 0x0E;
+                    self.color = if self.italic { 0 } else { (attribute / 2) as usize };
+                }
+                if let Mode::RollUp(_) = self.mode {
+                    // Roll-up captions keep their rows; a PAC only moves the base row.
+                    let shift = row as i64 + 1 - self.row as i64;
+                    if shift != 0 {
+                        self.displayed = std::mem::take(&mut self.displayed)
+                            .into_iter()
+                            .map(|(r, cells)| ((r as i64 + shift).clamp(1, ROWS as i64) as usize, cells))
+                            .collect();
+                    }
+                }
+                self.row = row + 1;
+            }
+            _ => {}
+        }
+    }
+
+    // Closes the caption on screen if the displayed memory changed, opening a new one for the new content.
+    fn refresh(&mut self, frame: i64) {
+        let displayed = self.displayed.clone();
+        self.refresh_to(frame, displayed);
+    }
+
+    fn refresh_to(&mut self, frame: i64, displayed: Memory) {
+        let text_of = |memory: &Memory| text_from_memory(memory);
+        if let Some((_, open)) = &self.open
+            && text_of(open) == text_of(&displayed)
+        {
+            return;
+        }
+        if let Some((start, memory)) = self.open.take()
+            && frame > start
+        {
+            self.cues.push((start, frame, memory));
+        }
+        if !text_of(&displayed).is_empty() {
+            self.open = Some((frame, displayed));
+        }
+    }
+}
+
+fn basic_char(byte: u8) -> char {
+    BASIC_OVERRIDES
+        .iter()
+        .find(|(code, _)| *code == byte)
+        .map(|(_, ch)| *ch)
+        .unwrap_or(byte as char)
+}
+
+fn text_from_memory(memory: &Memory) -> String {
+    memory
+        .values()
+        .map(row_text)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn row_text(cells: &BTreeMap<usize, Cell>) -> String {
+    let mut line = String::new();
+    let mut next_column = None;
+    for (column, cell) in cells {
+        // Tabbed-over gaps inside a row read as spaces.
+        if let Some(next) = next_column {
+            for _ in next..*column {
+                line.push(' ');
+            }
+        }
+        line.push(cell.ch);
+        next_column = Some(column + 1);
+    }
+    line.trim().to_string()
+}
+
+fn caption_from_memory(start: i64, end: i64, memory: &Memory, base: &CaptionStyle) -> Option<Caption> {
+    let text = text_from_memory(memory);
+    if text.is_empty() {
+        return None;
+    }
+
+    let rows: Vec<usize> = memory.iter().filter(|(_, c)| !row_text(c).is_empty()).map(|(r, _)| *r).collect();
+    let cells: Vec<&Cell> = memory.values().flat_map(|c| c.values()).filter(|c| c.ch != ' ').collect();
+
+    let mut style = base.clone();
+    let first_row = *rows.first()?;
+    let last_row = *rows.last()?;
+    style.position = if last_row >= 12 {
+        Position::Bottom
+    } else if first_row <= 3 {
+        Position::Top
+    } else if (first_row + last_row) / 2 == 8 {
+        Position::Middle
+    } else {
+        Position::Custom(50, ((first_row + last_row) as f32 / 2.0 / ROWS as f32 * 100.0).round() as i32)
+    };
+
+    // Judge alignment from where the text sits on the 32-column grid.
+    let spans: Vec<(usize, usize)> = memory
+        .values()
+        .filter_map(|cells| {
+            let visible: Vec<usize> = cells.iter().filter(|(_, c)| c.ch != ' ').map(|(col, _)| *col).collect();
+            Some((*visible.first()?, *visible.last()?))
+        })
+        .collect();
+    let lefts_match = spans.windows(2).all(|w| w[0].0 == w[1].0);
+    let rights_match = spans.windows(2).all(|w| w[0].1 == w[1].1);
+    let centered = spans.iter().all(|(l, r)| (l + r).abs_diff(COLUMNS - 1) <= 2);
+    style.alignment = match spans.as_slice() {
+        _ if centered => TextAlign::Center,
+        [(left, _)] if *left <= 2 => TextAlign::Left,
+        [(_, right)] if *right >= COLUMNS - 3 => TextAlign::Right,
+        [_, _, ..] if lefts_match && !rights_match => TextAlign::Left,
+        [_, _, ..] if rights_match && !lefts_match => TextAlign::Right,
+        _ => TextAlign::Center,
+    };
+
+    style.italic = cells.iter().any(|c| c.italic);
+    style.underline = cells.iter().any(|c| c.underline);
+    if let Some(cell) = cells.iter().find(|c| c.color > 0) {
+        style.color = COLORS[cell.color].to_string();
+    }
+
+    Some(Caption {
+        id: String::new(),
+        start_ms: FRAME_RATE.frames_to_ms(start),
+        end_ms: FRAME_RATE.frames_to_ms(end),
+        text,
+        speaker: None,
+        confidence: 1.0,
+        style,
+        words: Vec::new(),
+        track: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captioneditor::CaptionEditor;
+    use crate::structs::caption;
+
+    fn round_trip(captions: &[Caption]) -> Vec<Caption> {
+        parse(&write(captions), &CaptionEditor::default_style()).unwrap()
+    }
+
+    #[test]
+    fn round_trips_pop_on_captions() {
+        let parsed = round_trip(&[caption("1", 2000, 4000, "Hello, world!"), caption("2", 5000, 7000, "Two\nlines")]);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].text, "Hello, world!");
+        assert_eq!(parsed[1].text, "Two\nlines");
+        for (parsed, start, end) in [(&parsed[0], 2000, 4000), (&parsed[1], 5000, 7000)] {
+            assert!((parsed.start_ms - start).abs() <= 34, "start {}", parsed.start_ms);
+            assert!((parsed.end_ms - end).abs() <= 34, "end {}", parsed.end_ms);
+        }
+    }
+
+    #[test]
+    fn full_width_italic_line_keeps_every_character() {
+        let text = "This line is exactly thirty-two.";
+        assert_eq!(text.chars().count(), COLUMNS);
+        let mut italic = caption("1", 1000, 3000, text);
+        italic.style.italic = true;
+
+        let parsed = round_trip(&[italic]);
+        assert_eq!(parsed.len(), 1);
+        assert!(parsed[0].style.italic);
+        assert_eq!(parsed[0].text.split_whitespace().collect::<Vec<_>>().join(" "), text);
+    }
+
+    #[test]
+    fn wraps_to_available_width() {
+        assert_eq!(wrap("This line is exactly thirty-two.", COLUMNS), ["This line is exactly thirty-two."]);
+        assert_eq!(wrap("This line is exactly thirty-two.", COLUMNS - 1), ["This line is exactly", "thirty-two."]);
+        assert_eq!(wrap(&"x".repeat(32), COLUMNS - 1), ["x".repeat(31), "x".to_string()]);
+    }
+}
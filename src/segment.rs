@@ -0,0 +1,215 @@
+use crate::structs::{Caption, Word};
+use crate::words::{self, BreakWeights};
+use serde::{Deserialize, Serialize};
+
+// Limits for auto_segment. `max_chars` counts the whole cue, across lines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    pub max_duration_ms: i32,
+    pub min_duration_ms: i32,
+    pub max_chars: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules { max_duration_ms: 7000, min_duration_ms: 1000, max_chars: 84 }
+    }
+}
+
+// Cut weights, against a cost of 10 for each cue.
+const WEIGHTS: BreakWeights = BreakWeights { sentence_end: 4.0, clause_end: 2.0, clause_word: 1.0, binding: 4.0 };
+
+// A pause between words at least this long is a natural place to cut.
+const PAUSE_MS: i32 = 300;
+
+struct Token<'a> {
+    offset: usize,
+    text: &'a str,
+    start_ms: i32,
+    end_ms: i32,
+}
+
+// Splits a caption into cues within the rules, cutting at sentence and clause
+// boundaries, pauses between words and silences (`silent(time_ms)`) where it
+// can. Timings come from the word timestamps when they match the text, or else
+// are shared out by character count. Cues after the first have empty ids.
+pub fn segment(caption: &Caption, rules: &Rules, silent: &dyn Fn(i32) -> bool) -> Vec<Caption> {
+    let duration_ms = caption.end_ms - caption.start_ms;
+    if duration_ms <= rules.max_duration_ms && caption.text.chars().count() <= rules.max_chars {
+        return vec![caption.clone()];
+    }
+
+    let tokens = tokens(caption);
+    if tokens.len() < 2 {
+        return vec![caption.clone()];
+    }
+    let n = tokens.len();
+
+    // A cue runs from its first token to the next cue's first token (or the caption end).
+    let cue_start = |i: usize| if i == 0 { caption.start_ms } else { tokens[i].start_ms };
+    let cue_end = |j: usize| if j == n { caption.end_ms } else { tokens[j].start_ms };
+    let cue_chars = |i: usize, j: usize| {
+        let end = tokens.get(j).map_or(caption.text.len(), |t| t.offset);
+        caption.text[tokens[i].offset..end].trim().chars().count()
+    };
+
+    // The token indices where cues start, plus n, for the lowest total cost.
+    // When `strict`, no cue may be shorter than the minimum duration.
+    let plan = |strict: bool| -> Option<Vec<usize>> {
+        // best[j]: the lowest cost of cutting tokens[..j] into cues, and where the last cue starts.
+        let mut best: Vec<(f32, usize)> = vec![(f32::INFINITY, 0); n + 1];
+        best[0] = (0.0, 0);
+        for j in 1..=n {
+            for i in (0..j).rev() {
+                let (chars, duration) = (cue_chars(i, j), cue_end(j) - cue_start(i));
+                let fits = chars <= rules.max_chars && duration <= rules.max_duration_ms;
+                // A single token always makes a cue, so the lenient plan always has a solution.
+                if !fits && j - i > 1 {
+                    break;
+                }
+                let short = duration < rules.min_duration_ms;
+                if strict && short {
+                    continue;
+                }
+                let mut cost = best[i].0 + 10.0;
+                if short {
+                    cost += 5.0;
+                }
+                if j < n {
+                    cost += cut_cost(&tokens[j - 1], &tokens[j], silent);
+                }
+                if cost < best[j].0 {
+                    best[j] = (cost, i);
+                }
+            }
+        }
+        if !best[n].0.is_finite() {
+            return None;
+        }
+
+        let mut cuts = vec![n];
+        while let Some(&j) = cuts.last()
+            && j > 0
+        {
+            cuts.push(best[j].1);
+        }
+        cuts.reverse();
+        Some(cuts)
+    };
+    let cuts = plan(true).or_else(|| plan(false)).unwrap_or_else(|| vec![0, n]);
+
+    let matched = words::matches_text(&caption.words, &caption.text) && caption.words.len() == n;
+    cuts.windows(2)
+        .enumerate()
+        .map(|(k, cut)| {
+            let (i, j) = (cut[0], cut[1]);
+            let end = tokens.get(j).map_or(caption.text.len(), |t| t.offset);
+            let start_ms = cue_start(i);
+            let mut end_ms = cue_end(j);
+            // With word timings a cue ends with its last word, unless that is too short.
+            if matched && j < n {
+                end_ms = tokens[j - 1].end_ms.max(start_ms + rules.min_duration_ms).min(end_ms);
+            }
+            let cue_words: Vec<Word> = if matched {
+                caption.words[i..j].to_vec()
+            } else {
+                caption.words.iter().filter(|w| w.start_ms >= start_ms && w.start_ms < end_ms).cloned().collect()
+            };
+
+            Caption {
+                id: if k == 0 { caption.id.clone() } else { String::new() },
+                start_ms,
+                end_ms,
+                text: caption.text[tokens[i].offset..end].trim().to_string(),
+                words: cue_words,
+                ..caption.clone()
+            }
+        })
+        .collect()
+}
+
+// Tokens of the caption text with their timings, from the words when they
+// match or else in proportion to the characters before each token.
+fn tokens(caption: &Caption) -> Vec<Token<'_>> {
+    let spans = words::token_spans(&caption.text);
+    if words::matches_text(&caption.words, &caption.text) && caption.words.len() == spans.len() {
+        return spans
+            .into_iter()
+            .zip(&caption.words)
+            .map(|((offset, text), word)| Token { offset, text, start_ms: word.start_ms, end_ms: word.end_ms })
+            .collect();
+    }
+
+    let total: usize = spans.iter().map(|(_, t)| t.chars().count()).sum::<usize>().max(1);
+    let duration = (caption.end_ms - caption.start_ms) as i64;
+    let at = |chars: usize| caption.start_ms + (duration * chars as i64 / total as i64) as i32;
+    let mut before = 0;
+    spans
+        .into_iter()
+        .map(|(offset, text)| {
+            let start_ms = at(before);
+            before += text.chars().count();
+            Token { offset, text, start_ms, end_ms: at(before) }
+        })
+        .collect()
+}
+
+// The cost of cutting between two tokens; negative where a cut reads naturally.
+fn cut_cost(before: &Token, after: &Token, silent: &dyn Fn(i32) -> bool) -> f32 {
+    let mut cost = words::break_cost(before.text, after.text, &WEIGHTS);
+    if after.start_ms - before.end_ms >= PAUSE_MS {
+        cost -= 3.0;
+    } else if silent((before.end_ms + after.start_ms) / 2) {
+        cost -= 2.0;
+    }
+    cost
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::caption;
+
+    fn texts(cues: &[Caption]) -> Vec<&str> {
+        cues.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn leaves_captions_within_the_rules_alone() {
+        let short = caption("c1", 0, 3000, "Short enough.");
+        assert_eq!(segment(&short, &Rules::default(), &|_| false), vec![short]);
+    }
+
+    #[test]
+    fn cuts_at_sentence_ends() {
+        let long = caption("c1", 0, 10_000, "The storm came in fast. We ran for the barn and waited it out.");
+        let cues = segment(&long, &Rules::default(), &|_| false);
+        assert_eq!(texts(&cues), ["The storm came in fast.", "We ran for the barn and waited it out."]);
+        assert_eq!(cues[0].id, "c1");
+        assert_eq!(cues[1].id, "");
+        assert_eq!((cues[0].start_ms, cues[1].end_ms), (0, 10_000));
+        assert_eq!(cues[0].end_ms, cues[1].start_ms);
+    }
+
+    #[test]
+    fn minimum_duration_is_kept_when_a_cut_allows_it() {
+        // The sentence end after "Yes." would leave a cue under a second.
+        let rules = Rules { max_duration_ms: 4000, min_duration_ms: 1000, max_chars: 84 };
+        let long = caption("c1", 0, 6000, "Yes. I think we should go now and then come back for the rest tomorrow");
+        let cues = segment(&long, &rules, &|_| false);
+        assert!(cues.len() > 1);
+        for cue in &cues {
+            assert!(cue.end_ms - cue.start_ms >= rules.min_duration_ms, "{:?}", texts(&cues));
+            assert!(cue.end_ms - cue.start_ms <= rules.max_duration_ms, "{:?}", texts(&cues));
+        }
+        assert_ne!(cues[0].text, "Yes.");
+    }
+
+    #[test]
+    fn short_cues_are_allowed_when_nothing_else_fits() {
+        let rules = Rules { max_duration_ms: 500, min_duration_ms: 1000, max_chars: 84 };
+        let cues = segment(&caption("c1", 0, 1500, "one two three"), &rules, &|_| false);
+        assert_eq!(texts(&cues), ["one", "two", "three"]);
+    }
+}
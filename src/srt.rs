@@ -0,0 +1,231 @@
+use crate::structs::{Caption, CaptionStyle, ImportWarning};
+use wasm_bindgen::prelude::*;
+
+struct Cue {
+    line: usize,
+    start_ms: i32,
+    end_ms: i32,
+    text: Vec<String>,
+}
+
+// Parses SRT line by line so that CRLF files, whitespace-only separators,
+// missing index lines and missing blank lines between cues all recover.
+// Malformed blocks are skipped with a warning naming their first line, or
+// fail the whole import in strict mode.
+// Returned captions have empty ids; the editor assigns them.
+pub fn parse(
+    content: &str,
+    base: &CaptionStyle,
+    strict: bool,
+) -> Result<(Vec<Caption>, Vec<ImportWarning>), JsValue> {
+    let normalized = content.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+
+    let mut captions = Vec::new();
+    let mut warnings = Vec::new();
+    let mut warn = |line: usize, message: String| -> Result<(), JsValue> {
+        if strict {
+            return Err(JsValue::from_str(&format!("Line {}: {}", line, message)));
+        }
+        log::warn!("SRT line {}: {}", line, message);
+        warnings.push(ImportWarning { line, message });
+        Ok(())
+    };
+
+    let mut current: Option<Cue> = None;
+    let mut index_line: Option<usize> = None;
+    // Set after a timing line fails to parse, until the next blank line.
+    let mut skipping = false;
+
+    for (i, raw) in normalized.lines().enumerate() {
+        let line_number = i + 1;
+        let line = raw.trim();
+
+        if line.is_empty() {
+            if let Some(cue) = current.take() {
+                finish(cue, base, &mut captions, &mut warn)?;
+            }
+            index_line = None;
+            skipping = false;
+            continue;
+        }
+
+        if line.contains("-->") {
+            match parse_timing(line) {
+                Ok((start_ms, end_ms)) => {
+                    let mut has_index = index_line.take().is_some();
+                    if let Some(mut cue) = current.take() {
+                        // No blank line before this cue: its index ended up in the previous text.
+                        warn(line_number, String::from("Missing blank line before cue"))?;
+                        if cue.text.last().is_some_and(|t| is_index(t)) {
+                            cue.text.pop();
+                            has_index = true;
+                        }
+                        finish(cue, base, &mut captions, &mut warn)?;
+                    }
+                    let start_line = if has_index { line_number - 1 } else { line_number };
+                    if !has_index {
+                        warn(line_number, String::from("Missing cue index"))?;
+                    }
+                    current = Some(Cue { line: start_line, start_ms, end_ms, text: Vec::new() });
+                    skipping = false;
+                }
+                Err(message) => {
+                    warn(line_number, message)?;
+                    if let Some(cue) = current.take() {
+                        finish(cue, base, &mut captions, &mut warn)?;
+                    }
+                    index_line = None;
+                    skipping = true;
+                }
+            }
+            continue;
+        }
+
+        if skipping {
+            continue;
+        }
+        if let Some(cue) = current.as_mut() {
+            cue.text.push(raw.trim_end().to_string());
+        } else if index_line.is_none() && is_index(line) {
+            index_line = Some(line_number);
+        } else {
+            warn(line_number, format!("Text outside a cue: {:?}", line))?;
+            skipping = true;
+        }
+    }
+
+    if let Some(cue) = current.take() {
+        finish(cue, base, &mut captions, &mut warn)?;
+    }
+    if let Some(line) = index_line {
+        warn(line, String::from("Cue index without a timing line"))?;
+    }
+
+    captions.sort_by_key(|c| c.start_ms);
+    Ok((captions, warnings))
+}
+
+fn finish(
+    cue: Cue,
+    base: &CaptionStyle,
+    captions: &mut Vec<Caption>,
+    warn: &mut impl FnMut(usize, String) -> Result<(), JsValue>,
+) -> Result<(), JsValue> {
+    let text = cue.text.join("\n").trim().to_string();
+    if text.is_empty() {
+        return warn(cue.line, String::from("Cue has no text"));
+    }
+    if cue.end_ms < cue.start_ms {
+        return warn(cue.line, String::from("Cue ends before it starts"));
+    }
+    captions.push(Caption {
+        id: String::new(),
+        start_ms: cue.start_ms,
+        end_ms: cue.end_ms,
+        text,
+        speaker: None,
+        confidence: 1.0,
+        style: base.clone(),
+        words: Vec::new(),
+        track: None,
+    });
+    Ok(())
+}
+
+fn is_index(line: &str) -> bool {
+    !line.is_empty() && line.trim().chars().all(|c| c.is_ascii_digit())
+}
+
+// Parses "start --> end", ignoring spacing around the arrow and any trailing
+// "X1:… X2:… Y1:… Y2:…" box coordinates.
+fn parse_timing(line: &str) -> Result<(i32, i32), String> {
+    let (start, rest) = line.split_once("-->").ok_or_else(|| format!("Invalid timing line: {:?}", line))?;
+    let end = rest.split_whitespace().next().unwrap_or("");
+
+    let start_ms = parse_timestamp(start).map_err(|e| format!("{} in timing line {:?}", e, line))?;
+    let end_ms = parse_timestamp(end).map_err(|e| format!("{} in timing line {:?}", e, line))?;
+    Ok((start_ms, end_ms))
+}
+
+// Parses HH:MM:SS,mmm, also accepting '.' before the milliseconds.
+pub fn parse_timestamp(timestamp: &str) -> Result<i32, String> {
+    let trimmed = timestamp.trim().replace(',', ".");
+    let parts: Vec<&str> = trimmed.split(':').collect();
+    if parts.len() != 3 {
+        return Err(format!("Invalid timestamp format: {}", timestamp));
+    }
+
+    let hours: i32 = parts[0].parse().map_err(|_| String::from("Invalid hours"))?;
+    let minutes: i32 = parts[1].parse().map_err(|_| String::from("Invalid minutes"))?;
+
+    let seconds_parts: Vec<&str> = parts[2].split('.').collect();
+    let seconds: i32 = seconds_parts[0].parse().map_err(|_| String::from("Invalid seconds"))?;
+    let milliseconds: i32 = if seconds_parts.len() > 1 {
+        // Pad with zeros if necessary (e.g., ".5" becomes ".500").
+        let padded_ms = format!("{:0<3}", seconds_parts[1]);
+        padded_ms
+            .get(..3)
+            .and_then(|ms| ms.parse().ok())
+            .ok_or_else(|| String::from("Invalid milliseconds"))?
+    } else {
+        0
+    };
+
+    Ok(hours * 3600000 + minutes * 60000 + seconds * 1000 + milliseconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captioneditor::CaptionEditor;
+
+    fn messages(warnings: &[ImportWarning]) -> Vec<(usize, &str)> {
+        warnings.iter().map(|w| (w.line, w.message.as_str())).collect()
+    }
+
+    #[test]
+    fn strict_mode_accepts_a_clean_file() {
+        let content = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\nthere\r\n\r\n\
+            2\r\n00:00:03.5 --> 01:00:04,250 X1:100 X2:600 Y1:50 Y2:80\r\nSecond\r\n";
+        let (captions, warnings) = parse(content, &CaptionEditor::default_style(), true).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(captions.len(), 2);
+        assert_eq!((captions[0].start_ms, captions[0].end_ms), (1000, 2500));
+        assert_eq!(captions[0].text, "Hello\nthere");
+        assert_eq!((captions[1].start_ms, captions[1].end_ms), (3500, 3_604_250));
+        assert_eq!(captions[1].text, "Second");
+    }
+
+    #[test]
+    fn lenient_mode_recovers_and_reports_line_numbers() {
+        let content = "1\n00:00:01,000 --> 00:00:02,000\nFirst\n2\n00:00:03,000 --> 00:00:04,000\nSecond\n \n\
+            00:00:05,000 --> 00:00:06,000\nNo index\n\n\
+            4\n00:00:07,000 -> 00:00:08,000\nBad arrow\n\n\
+            5\n00:00:09,000 --> 00:00:10,000\n\n\
+            stray text\n\n\
+            6\n00:00:12,000 --> 00:00:11,000\nBackwards\n";
+        let (captions, warnings) = parse(content, &CaptionEditor::default_style(), false).unwrap();
+
+        let texts: Vec<&str> = captions.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["First", "Second", "No index"]);
+        assert_eq!(
+            messages(&warnings),
+            [
+                (5, "Missing blank line before cue"),
+                (8, "Missing cue index"),
+                (12, "Text outside a cue: \"00:00:07,000 -> 00:00:08,000\""),
+                (15, "Cue has no text"),
+                (18, "Text outside a cue: \"stray text\""),
+                (20, "Cue ends before it starts"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_timestamp_variants() {
+        assert_eq!(parse_timestamp("01:02:03,004"), Ok(3_723_004));
+        assert_eq!(parse_timestamp(" 00:00:01.5 "), Ok(1500));
+        assert_eq!(parse_timestamp("00:00:01"), Ok(1000));
+        assert!(parse_timestamp("00:01,000").is_err());
+    }
+}
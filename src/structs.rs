@@ -3,7 +3,7 @@ use web_sys::{console, HtmlVideoElement, AudioContext};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Caption {
     pub id: String,
     pub start_ms: i32,
@@ -12,9 +12,21 @@ pub struct Caption {
     pub speaker: Option<String>,
     pub confidence: f32,
     pub style: CaptionStyle,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<Word>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track: Option<String>, // track or language code, e.g. "en"
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Word {
+    pub text: String,
+    pub start_ms: i32,
+    pub end_ms: i32,
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CaptionStyle {
     pub position: Position,
     pub font_size: u32,
@@ -32,26 +44,70 @@ pub struct CaptionStyle {
     pub shadow_offset_x: i32,
     pub shadow_offset_y: i32,
 
-    pub animation_type: Option<String>, // "fade", "slide", "bounce", "typewriter"
+    pub animation_type: Option<String>, // "fade", "slide", "bounce", "typewriter", "karaoke"
     pub animation_duration: f32, // in seconds
     pub shadow_blur: f32,
     pub border_radius: f32,
     pub letter_spacing: f32,
+    #[serde(default)]
+    pub width_percent: Option<f32>, // caption box width as % of the frame
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Position {
     Bottom,
     Top,
     Middle,
-    Custom(i32, i32),
+    Custom(i32, i32), // x, y as % of the frame, anchored at the caption center
 }
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TextAlign {
     Left,
     Center,
     Right
 }
 
+// A recoverable problem found while importing, with the 1-based source line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportWarning {
+    pub line: usize,
+    pub message: String,
+}
+
+// Two captions on the same track that overlap in time, `first_id` starting first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub first_id: String,
+    pub second_id: String,
+    pub overlap_ms: i32,
+}
+
+// A QC rule violation. `value` is what was measured and `limit` the profile's
+// limit for `rule`, in the rule's unit (ms, frames, characters or lines).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QcIssue {
+    pub caption_id: String,
+    pub rule: String,
+    pub severity: String, // "error" or "warning"
+    pub value: f64,
+    pub limit: f64,
+    pub start_ms: i32,
+}
+
+// A default-styled caption for tests.
+#[cfg(test)]
+pub fn caption(id: &str, start_ms: i32, end_ms: i32, text: &str) -> Caption {
+    Caption {
+        id: id.to_string(),
+        start_ms,
+        end_ms,
+        text: text.to_string(),
+        speaker: None,
+        confidence: 1.0,
+        style: crate::captioneditor::CaptionEditor::default_style(),
+        words: Vec::new(),
+        track: None,
+    }
+}
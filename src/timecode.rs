@@ -0,0 +1,103 @@
+use wasm_bindgen::prelude::*;
+
+// An exact frame rate as a rational number, e.g. 30000/1001 for 29.97.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameRate {
+    pub numerator: i64,
+    pub denominator: i64,
+    pub drop_frame: bool,
+}
+
+impl FrameRate {
+    pub fn from_fps(fps: f64, drop_frame: bool) -> Result<FrameRate, JsValue> {
+        let (numerator, denominator) = match (fps * 1000.0).round() as i64 {
+            23976 => (24000, 1001),
+            24000 => (24, 1),
+            25000 => (25, 1),
+            29970 => (30000, 1001),
+            30000 => (30, 1),
+            50000 => (50, 1),
+            59940 => (60000, 1001),
+            60000 => (60, 1),
+            _ => return Err(JsValue::from_str(&format!("Unsupported frame rate: {}", fps))),
+        };
+
+        if drop_frame && denominator != 1001 {
+            return Err(JsValue::from_str("Drop-frame timecode requires 29.97 or 59.94 fps"));
+        }
+
+        Ok(FrameRate { numerator, denominator, drop_frame })
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    // Frames per second as counted by timecode (30 for 29.97).
+    pub fn nominal_fps(&self) -> i64 {
+        (self.numerator + self.denominator - 1) / self.denominator
+    }
+
+    pub fn ms_to_frames(&self, ms: i32) -> i64 {
+        (ms as i64 * self.numerator + self.denominator * 500) / (self.denominator * 1000)
+    }
+
+    pub fn frames_to_ms(&self, frames: i64) -> i32 {
+        ((frames * self.denominator * 1000 + self.numerator / 2) / self.numerator) as i32
+    }
+
+    // Parses HH:MM:SS:FF (any of ':', ';' or '.' before the frames) into a frame count.
+    pub fn timecode_to_frames(&self, timecode: &str) -> Option<i64> {
+        let fields: Vec<i64> = timecode
+            .trim()
+            .split([':', ';', '.'])
+            .map(|f| f.parse().ok())
+            .collect::<Option<_>>()?;
+        let [hours, minutes, seconds, frames] = fields[..] else {
+            return None;
+        };
+
+        let fps = self.nominal_fps();
+        let mut total = ((hours * 60 + minutes) * 60 + seconds) * fps + frames;
+        if self.drop_frame {
+            let total_minutes = hours * 60 + minutes;
+            total -= (fps / 15) * (total_minutes - total_minutes / 10);
+        }
+        Some(total)
+    }
+
+    // Formats a frame count as HH:MM:SS:FF, or HH:MM:SS;FF for drop-frame.
+    pub fn frames_to_timecode(&self, frames: i64) -> String {
+        let fps = self.nominal_fps();
+        let mut frames = frames.max(0);
+
+        if self.drop_frame {
+            // Frame numbers 0 and 1 (0-3 at 59.94) are skipped every minute except every tenth.
+            let dropped = fps / 15;
+            let frames_per_minute = fps * 60 - dropped;
+            let frames_per_ten_minutes = frames_per_minute * 10 + dropped;
+
+            let tens = frames / frames_per_ten_minutes;
+            let remainder = frames % frames_per_ten_minutes;
+            frames += dropped * 9 * tens;
+            if remainder > dropped {
+                frames += dropped * ((remainder - dropped) / frames_per_minute);
+            }
+        }
+
+        format!(
+            "{:02}:{:02}:{:02}{}{:02}",
+            frames / (fps * 3600),
+            (frames / (fps * 60)) % 60,
+            (frames / fps) % 60,
+            if self.drop_frame { ';' } else { ':' },
+            frames % fps
+        )
+    }
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        FrameRate { numerator: 30, denominator: 1, drop_frame: false }
+    }
+}
@@ -0,0 +1,783 @@
+use crate::ass;
+use crate::fcpxml::escape_xml;
+use crate::structs::{Caption, CaptionStyle, Position, TextAlign};
+use crate::timecode::FrameRate;
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+// Exports use 10MHz ticks, the rate most SMPTE-TT/DFXP deliveries expect.
+const EXPORT_TICK_RATE: i64 = 10_000_000;
+
+// IMSC1 measures font sizes in cells; the default cell grid is 32x15.
+const CELL_ROWS: f32 = 15.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Profile {
+    Imsc1Text, // clock-time expressions, e.g. "00:00:01.500"
+    SmpteTt,   // tick-based offset times, e.g. "15000000t"
+}
+
+struct TimingParams {
+    frame_rate: f64,
+    sub_frame_rate: f64,
+    tick_rate: f64,
+}
+
+struct Context<'a, 'input> {
+    params: TimingParams,
+    styles: HashMap<&'a str, Node<'a, 'input>>,
+    regions: HashMap<&'a str, Node<'a, 'input>>,
+    agents: HashMap<&'a str, &'a str>, // ttm:agent id to display name
+    root_extent: Option<(f32, f32)>, // pixels, when the document declares tts:extent
+}
+
+// Parses a TTML document (IMSC1, SMPTE-TT or legacy DFXP) into captions.
+// Returned captions have empty ids; the editor assigns them.
+pub fn parse(content: &str, base: &CaptionStyle) -> Result<Vec<Caption>, JsValue> {
+    let document = Document::parse(content.trim_start_matches('\u{feff}'))
+        .map_err(|e| JsValue::from_str(&format!("Invalid TTML document: {}", e)))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "tt" {
+        return Err(JsValue::from_str("Invalid TTML document: root element must be <tt>"));
+    }
+
+    let mut styles = HashMap::new();
+    let mut regions = HashMap::new();
+    let mut agents = HashMap::new();
+    for node in root.descendants().filter(Node::is_element) {
+        let Some(id) = xml_id(node) else {
+            continue;
+        };
+        match node.tag_name().name() {
+            "style" => {
+                styles.insert(id, node);
+            }
+            "region" => {
+                regions.insert(id, node);
+            }
+            "agent" => {
+                // Prefer the agent's full name, then any name, then its id.
+                let names: Vec<Node> = node.children().filter(|n| n.tag_name().name() == "name").collect();
+                let name = names
+                    .iter()
+                    .find(|n| n.attribute("type") == Some("full"))
+                    .or(names.first())
+                    .and_then(|n| n.text())
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or(id);
+                agents.insert(id, name);
+            }
+            _ => {}
+        }
+    }
+
+    let context = Context {
+        params: timing_params(root),
+        styles,
+        regions,
+        agents,
+        root_extent: tts(root, "extent").and_then(|e| {
+            let (w, h) = pair(e)?;
+            Some((w.strip_suffix("px")?.parse().ok()?, h.strip_suffix("px")?.parse().ok()?))
+        }),
+    };
+
+    // Relative font sizes resolve against TTML's initial size of one cell.
+    let mut initial = base.clone();
+    initial.font_size = (ass::PLAY_RES_Y / CELL_ROWS).round() as u32;
+
+    let mut captions = Vec::new();
+    if let Some(body) = root.children().find(|n| n.tag_name().name() == "body") {
+        collect(body, &context, 0.0, &initial, None, &mut captions)?;
+    }
+
+    captions.sort_by_key(|c| c.start_ms);
+    Ok(captions)
+}
+
+// Walks body/div containers, accumulating timing offsets, inherited styles and regions.
+fn collect<'a, 'input>(
+    node: Node<'a, 'input>,
+    context: &Context<'a, 'input>,
+    parent_begin_ms: f64,
+    inherited: &CaptionStyle,
+    inherited_region: Option<&'a str>,
+    captions: &mut Vec<Caption>,
+) -> Result<(), JsValue> {
+    let region = node.attribute("region").or(inherited_region);
+
+    if node.tag_name().name() == "p" {
+        if let Some(caption) = paragraph(node, context, parent_begin_ms, inherited.clone(), region)? {
+            captions.push(caption);
+        }
+        return Ok(());
+    }
+
+    let mut style = inherited.clone();
+    apply_styling(node, context, &mut style);
+    let begin_ms = parent_begin_ms + time_attribute(node, "begin", &context.params)?.unwrap_or(0.0);
+
+    for child in node.children().filter(Node::is_element) {
+        collect(child, context, begin_ms, &style, region, captions)?;
+    }
+    Ok(())
+}
+
+fn paragraph<'a, 'input>(
+    node: Node<'a, 'input>,
+    context: &Context<'a, 'input>,
+    parent_begin_ms: f64,
+    mut style: CaptionStyle,
+    region: Option<&'a str>,
+) -> Result<Option<Caption>, JsValue> {
+    let params = &context.params;
+    let mut begin = time_attribute(node, "begin", params)?.map(|t| parent_begin_ms + t);
+    let mut end = match time_attribute(node, "end", params)? {
+        Some(t) => Some(parent_begin_ms + t),
+        None => match (begin, time_attribute(node, "dur", params)?) {
+            (Some(b), Some(d)) => Some(b + d),
+            _ => None,
+        },
+    };
+
+    // Word-timed paragraphs may only carry timing on their spans.
+    if begin.is_none() || end.is_none() {
+        for span in node.descendants().filter(|n| n.tag_name().name() == "span") {
+            if let Some(b) = time_attribute(span, "begin", params)? {
+                let span_begin = parent_begin_ms + b;
+                begin = Some(begin.map_or(span_begin, |v: f64| v.min(span_begin)));
+                if let Some(e) = time_attribute(span, "end", params)? {
+                    let span_end = parent_begin_ms + e;
+                    end = Some(end.map_or(span_end, |v: f64| v.max(span_end)));
+                }
+            }
+        }
+    }
+
+    let (Some(begin), Some(end)) = (begin, end) else {
+        log::warn!("Skipping TTML paragraph without timing");
+        return Ok(None);
+    };
+
+    if let Some(region) = region.and_then(|id| context.regions.get(id)) {
+        apply_region(*region, context, &mut style);
+    }
+    // Paragraph styles take precedence over the region's.
+    apply_styling(node, context, &mut style);
+
+    let mut text = String::new();
+    paragraph_text(node, context, &mut style, &mut text);
+    let text = text
+        .split('\n')
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string();
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    // ttm:agent refers to one or more <ttm:agent> elements by id.
+    let speaker = node
+        .attributes()
+        .find(|a| a.name() == "agent")
+        .and_then(|a| a.value().split_whitespace().next())
+        .map(|id| context.agents.get(id).copied().unwrap_or(id).to_string());
+
+    Ok(Some(Caption {
+        id: String::new(),
+        start_ms: begin.round() as i32,
+        end_ms: end.round() as i32,
+        text,
+        speaker,
+        confidence: 1.0,
+        style,
+        words: Vec::new(),
+        track: None,
+    }))
+}
+
+// Flattens span and br content. Styles apply to the whole caption, so span styling is merged in.
+fn paragraph_text(node: Node, context: &Context, style: &mut CaptionStyle, text: &mut String) {
+    for child in node.children() {
+        if child.is_text() {
+            text.push_str(child.text().unwrap_or(""));
+        } else if child.tag_name().name() == "br" {
+            text.push('\n');
+        } else if child.tag_name().name() == "span" {
+            apply_styling(child, context, style);
+            paragraph_text(child, context, style, text);
+        }
+    }
+}
+
+fn xml_id<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    node.attribute((roxmltree::NS_XML_URI, "id"))
+        .or_else(|| node.attribute("id"))
+}
+
+// Styling and parameter attributes are matched by local name so that legacy DFXP
+// namespaces (e.g. http://www.w3.org/2006/10/ttaf1#styling) are accepted too.
+fn tts<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|a| a.name() == name && a.namespace().is_some_and(|ns| ns.ends_with("#styling") || ns.ends_with("#style")))
+        .map(|a| a.value())
+}
+
+fn ttp<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|a| a.name() == name && a.namespace().is_some_and(|ns| ns.ends_with("#parameter")))
+        .map(|a| a.value())
+}
+
+fn pair(value: &str) -> Option<(&str, &str)> {
+    let mut parts = value.split_whitespace();
+    Some((parts.next()?, parts.next()?))
+}
+
+fn timing_params(root: Node) -> TimingParams {
+    let declared_rate = ttp(root, "frameRate").and_then(|r| r.parse::<f64>().ok());
+    let multiplier = ttp(root, "frameRateMultiplier")
+        .and_then(pair)
+        .and_then(|(n, d)| Some(n.parse::<f64>().ok()? / d.parse::<f64>().ok()?))
+        .unwrap_or(1.0);
+    let frame_rate = declared_rate.unwrap_or(30.0) * multiplier;
+    let sub_frame_rate = ttp(root, "subFrameRate").and_then(|r| r.parse().ok()).unwrap_or(1.0);
+
+    // Without an explicit tick rate, ticks run at the frame rate if one is declared, else at 1Hz.
+    let tick_rate = ttp(root, "tickRate")
+        .and_then(|r| r.parse().ok())
+        .unwrap_or(if declared_rate.is_some() { frame_rate * sub_frame_rate } else { 1.0 });
+
+    TimingParams { frame_rate, sub_frame_rate, tick_rate }
+}
+
+fn time_attribute(node: Node, name: &str, params: &TimingParams) -> Result<Option<f64>, JsValue> {
+    match node.attribute(name) {
+        Some(value) => parse_time(value, params).map(Some),
+        None => Ok(None),
+    }
+}
+
+// Parses clock-time ("HH:MM:SS.fff", "HH:MM:SS:FF[.sub]") and offset-time ("1.5s", "900f", "15000000t") expressions into milliseconds.
+fn parse_time(value: &str, params: &TimingParams) -> Result<f64, JsValue> {
+    let invalid = || JsValue::from_str(&format!("Invalid TTML time expression: {}", value));
+    let value = value.trim();
+
+    if value.contains(':') {
+        let parts: Vec<&str> = value.split(':').collect();
+        if parts.len() < 3 || parts.len() > 4 {
+            return Err(invalid());
+        }
+        let hours: f64 = parts[0].parse().map_err(|_| invalid())?;
+        let minutes: f64 = parts[1].parse().map_err(|_| invalid())?;
+        let seconds: f64 = parts[2].parse().map_err(|_| invalid())?;
+        let mut total = hours * 3600.0 + minutes * 60.0 + seconds;
+
+        if let Some(frames) = parts.get(3) {
+            let (frames, sub_frames) = frames.split_once('.').unwrap_or((frames, "0"));
+            let frames: f64 = frames.parse().map_err(|_| invalid())?;
+            let sub_frames: f64 = sub_frames.parse().map_err(|_| invalid())?;
+            total += (frames + sub_frames / params.sub_frame_rate) / params.frame_rate;
+        }
+        return Ok(total * 1000.0);
+    }
+
+    let split = value.find(|c: char| c.is_ascii_alphabetic()).ok_or_else(invalid)?;
+    let (number, metric) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match metric {
+        "h" => number * 3600.0,
+        "m" => number * 60.0,
+        "s" => number,
+        "ms" => number / 1000.0,
+        "f" => number / params.frame_rate,
+        "t" => number / params.tick_rate,
+        _ => return Err(invalid()),
+    };
+    Ok(seconds * 1000.0)
+}
+
+// Applies referenced styles first, then the element's own tts:* attributes.
+fn apply_styling(node: Node, context: &Context, style: &mut CaptionStyle) {
+    if let Some(references) = node.attribute("style") {
+        for id in references.split_whitespace() {
+            if let Some(referenced) = context.styles.get(id) {
+                apply_styling(*referenced, context, style);
+            }
+        }
+    }
+    apply_tts(node, context, style);
+}
+
+fn apply_tts(node: Node, context: &Context, style: &mut CaptionStyle) {
+    if let Some(color) = tts(node, "color").and_then(parse_color) {
+        style.color = color;
+    }
+    if let Some(color) = tts(node, "backgroundColor").and_then(parse_color) {
+        // Region backgrounds fill the whole region rather than the text box, so only take them from content.
+        if node.tag_name().name() != "region" {
+            style.background = color;
+        }
+    }
+    if let Some(family) = tts(node, "fontFamily") {
+        let first = family.split(',').next().unwrap_or("").trim().trim_matches(|c| c == '"' || c == '\'');
+        style.font_family = match first {
+            "default" | "proportionalSansSerif" | "sansSerif" => String::from("Arial"),
+            "monospace" | "monospaceSansSerif" => String::from("Courier New"),
+            "proportionalSerif" | "serif" => String::from("Times New Roman"),
+            "monospaceSerif" => String::from("Courier"),
+            other => other.to_string(),
+        };
+    }
+    if let Some(size) = tts(node, "fontSize").and_then(|s| length_to_reference_px(s, style.font_size as f32, context)) {
+        style.font_size = size.round().max(1.0) as u32;
+    }
+    if let Some(weight) = tts(node, "fontWeight") {
+        style.bold = weight == "bold";
+    }
+    if let Some(font_style) = tts(node, "fontStyle") {
+        style.italic = font_style == "italic" || font_style == "oblique";
+    }
+    if let Some(decoration) = tts(node, "textDecoration") {
+        style.underline = decoration.split_whitespace().any(|d| d == "underline");
+    }
+    if let Some(align) = tts(node, "textAlign") {
+        style.alignment = match align {
+            "left" | "start" => TextAlign::Left,
+            "right" | "end" => TextAlign::Right,
+            _ => TextAlign::Center,
+        };
+    }
+    if let Some(outline) = tts(node, "textOutline") {
+        // "none", "<thickness>" or "<color> <thickness> [<blur>]"
+        let parts: Vec<&str> = outline.split_whitespace().collect();
+        if parts.first() == Some(&"none") {
+            style.outline_width = 0;
+            style.outline_color = None;
+        } else {
+            let (color, thickness) = match parts.as_slice() {
+                [thickness] => (None, *thickness),
+                [color, thickness, ..] => (parse_color(color), *thickness),
+                [] => (None, ""),
+            };
+            if let Some(width) = length_to_reference_px(thickness, style.font_size as f32, context) {
+                style.outline_width = width.round().max(1.0) as u32;
+                style.outline_color = color.or_else(|| Some(style.color.clone()));
+            }
+        }
+    }
+    if let Some(shadow) = tts(node, "textShadow") {
+        // "<x> <y> [<blur>] [<color>]", TTML2/IMSC1.1
+        let parts: Vec<&str> = shadow.split_whitespace().collect();
+        let lengths: Vec<f32> = parts
+            .iter()
+            .filter_map(|p| length_to_reference_px(p, style.font_size as f32, context))
+            .collect();
+        if let [x, y, rest @ ..] = lengths.as_slice() {
+            style.shadow_offset_x = x.round() as i32;
+            style.shadow_offset_y = y.round() as i32;
+            style.shadow_blur = rest.first().copied().unwrap_or(0.0);
+            style.shadow_color = parts.iter().find_map(|p| parse_color(p)).or_else(|| Some(String::from("#000000")));
+        }
+    }
+}
+
+// Resolves a TTML length to pixels in the ASS reference frame used by CaptionStyle.
+fn length_to_reference_px(value: &str, font_size: f32, context: &Context) -> Option<f32> {
+    let cell = ass::PLAY_RES_Y / CELL_ROWS;
+    let value = value.split_whitespace().next()?;
+    if let Some(percent) = value.strip_suffix('%') {
+        // Percentages are relative to the inherited font size.
+        return Some(percent.parse::<f32>().ok()? / 100.0 * font_size);
+    }
+    if let Some(cells) = value.strip_suffix('c') {
+        return Some(cells.parse::<f32>().ok()? * cell);
+    }
+    if let Some(em) = value.strip_suffix("em") {
+        return Some(em.parse::<f32>().ok()? * font_size);
+    }
+    if let Some(px) = value.strip_suffix("px") {
+        let px: f32 = px.parse().ok()?;
+        let height = context.root_extent.map(|(_, h)| h).unwrap_or(ass::PLAY_RES_Y);
+        return Some(px * ass::PLAY_RES_Y / height);
+    }
+    None
+}
+
+// Percentage of the frame for a region coordinate given in % or px.
+fn region_percent(value: &str, extent: Option<f32>) -> Option<f32> {
+    if let Some(percent) = value.strip_suffix('%') {
+        return percent.parse().ok();
+    }
+    let px: f32 = value.strip_suffix("px")?.parse().ok()?;
+    Some(px * 100.0 / extent?)
+}
+
+fn apply_region(region: Node, context: &Context, style: &mut CaptionStyle) {
+    apply_styling(region, context, style);
+
+    let (width, height) = context.root_extent.unzip();
+    let origin = tts(region, "origin")
+        .and_then(pair)
+        .and_then(|(x, y)| Some((region_percent(x, width)?, region_percent(y, height)?)))
+        .unwrap_or((0.0, 0.0));
+    let extent = tts(region, "extent")
+        .and_then(pair)
+        .and_then(|(w, h)| Some((region_percent(w, width)?, region_percent(h, height)?)))
+        .unwrap_or((100.0, 100.0));
+    let display_align = tts(region, "displayAlign").unwrap_or("before");
+
+    let (left, top) = origin;
+    let (region_width, region_height) = extent;
+    let bottom = top + region_height;
+
+    style.width_percent = if region_width < 100.0 { Some(region_width) } else { None };
+    style.position = match display_align {
+        "after" | "end" if bottom >= 85.0 => Position::Bottom,
+        "before" | "start" if top <= 15.0 => Position::Top,
+        "center" if (top + region_height / 2.0 - 50.0).abs() <= 5.0 => Position::Middle,
+        "after" | "end" => Position::Custom((left + region_width / 2.0).round() as i32, (bottom - 5.0).round() as i32),
+        "center" => Position::Custom(
+            (left + region_width / 2.0).round() as i32,
+            (top + region_height / 2.0).round() as i32,
+        ),
+        _ => Position::Custom((left + region_width / 2.0).round() as i32, (top + 5.0).round() as i32),
+    };
+}
+
+// Converts a TTML colour (#rrggbb[aa], rgb(), rgba() or a named colour) to "#RRGGBB[AA]".
+fn parse_color(value: &str) -> Option<String> {
+    let value = value.trim();
+    let (red, green, blue, alpha) = if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        match hex.len() {
+            6 => (channel(0)?, channel(2)?, channel(4)?, 255),
+            8 => (channel(0)?, channel(2)?, channel(4)?, channel(6)?),
+            _ => return None,
+        }
+    } else if let Some(args) = value.strip_prefix("rgba(").or_else(|| value.strip_prefix("rgb(")) {
+        let channels: Vec<u8> = args
+            .trim_end_matches(')')
+            .split(',')
+            .map(|c| c.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        match channels.as_slice() {
+            [r, g, b] => (*r, *g, *b, 255),
+            [r, g, b, a] => (*r, *g, *b, *a),
+            _ => return None,
+        }
+    } else {
+        match value.to_ascii_lowercase().as_str() {
+            "transparent" => (0, 0, 0, 0),
+            "black" => (0, 0, 0, 255),
+            "silver" => (192, 192, 192, 255),
+            "gray" => (128, 128, 128, 255),
+            "white" => (255, 255, 255, 255),
+            "maroon" => (128, 0, 0, 255),
+            "red" => (255, 0, 0, 255),
+            "purple" => (128, 0, 128, 255),
+            "fuchsia" | "magenta" => (255, 0, 255, 255),
+            "green" => (0, 128, 0, 255),
+            "lime" => (0, 255, 0, 255),
+            "olive" => (128, 128, 0, 255),
+            "yellow" => (255, 255, 0, 255),
+            "navy" => (0, 0, 128, 255),
+            "blue" => (0, 0, 255, 255),
+            "teal" => (0, 128, 128, 255),
+            "aqua" | "cyan" => (0, 255, 255, 255),
+            _ => return None,
+        }
+    };
+
+    Some(if alpha == 255 {
+        format!("#{:02X}{:02X}{:02X}", red, green, blue)
+    } else {
+        format!("#{:02X}{:02X}{:02X}{:02X}", red, green, blue, alpha)
+    })
+}
+
+// Writes a TTML document in the given profile, with one style per distinct
+// CaptionStyle and one region per distinct placement.
+pub fn write(captions: &[Caption], frame_rate: FrameRate, profile: Profile) -> String {
+    let mut styles: Vec<&CaptionStyle> = Vec::new();
+    let mut regions: Vec<(Position, Option<f32>)> = Vec::new();
+    let mut speakers: Vec<&str> = Vec::new();
+    for caption in captions {
+        if !styles.contains(&&caption.style) {
+            styles.push(&caption.style);
+        }
+        let placement = (caption.style.position.clone(), caption.style.width_percent);
+        if !regions.contains(&placement) {
+            regions.push(placement);
+        }
+        if let Some(speaker) = caption.speaker.as_deref()
+            && !speakers.contains(&speaker)
+        {
+            speakers.push(speaker);
+        }
+    }
+
+    let (profile_uri, time_params) = match profile {
+        Profile::Imsc1Text => ("http://www.w3.org/ns/ttml/profile/imsc1/text", String::new()),
+        Profile::SmpteTt => (
+            "http://www.smpte-ra.org/schemas/2052-1/2010/profiles/smpte-tt-full",
+            format!(" ttp:tickRate=\"{}\"", EXPORT_TICK_RATE),
+        ),
+    };
+    let multiplier = if frame_rate.denominator == 1 {
+        String::new()
+    } else {
+        format!(" ttp:frameRateMultiplier=\"1000 {}\"", frame_rate.denominator)
+    };
+
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str(&format!(
+        "<tt xmlns=\"http://www.w3.org/ns/ttml\" xmlns:ttp=\"http://www.w3.org/ns/ttml#parameter\" xmlns:tts=\"http://www.w3.org/ns/ttml#styling\" xmlns:ttm=\"http://www.w3.org/ns/ttml#metadata\" xml:lang=\"en\" ttp:timeBase=\"media\" ttp:frameRate=\"{}\"{}{} ttp:profile=\"{}\">\n",
+        frame_rate.nominal_fps(),
+        multiplier,
+        time_params,
+        profile_uri
+    ));
+    output.push_str("  <head>\n");
+    if !speakers.is_empty() {
+        output.push_str("    <metadata>\n");
+        for (i, speaker) in speakers.iter().enumerate() {
+            output.push_str(&format!(
+                "      <ttm:agent xml:id=\"a{}\" type=\"person\"><ttm:name type=\"full\">{}</ttm:name></ttm:agent>\n",
+                i + 1,
+                escape_xml(speaker)
+            ));
+        }
+        output.push_str("    </metadata>\n");
+    }
+    output.push_str("    <styling>\n");
+    for (i, style) in styles.iter().enumerate() {
+        output.push_str(&format!("      <style xml:id=\"s{}\"{}/>\n", i + 1, style_attributes(style)));
+    }
+    output.push_str("    </styling>\n    <layout>\n");
+    for (i, (position, width)) in regions.iter().enumerate() {
+        output.push_str(&format!("      <region xml:id=\"r{}\"{}/>\n", i + 1, region_attributes(position, *width)));
+    }
+    output.push_str("    </layout>\n  </head>\n  <body>\n    <div>\n");
+
+    for caption in captions {
+        let style_index = styles.iter().position(|s| **s == caption.style).unwrap_or(0);
+        let placement = (caption.style.position.clone(), caption.style.width_percent);
+        let region_index = regions.iter().position(|r| *r == placement).unwrap_or(0);
+        let text = caption
+            .text
+            .lines()
+            .map(escape_xml)
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        let agent = match caption.speaker.as_deref().and_then(|s| speakers.iter().position(|p| *p == s)) {
+            Some(index) => format!(" ttm:agent=\"a{}\"", index + 1),
+            None => String::new(),
+        };
+
+        output.push_str(&format!(
+            "      <p begin=\"{}\" end=\"{}\" region=\"r{}\" style=\"s{}\"{}>{}</p>\n",
+            format_time(caption.start_ms, profile),
+            format_time(caption.end_ms, profile),
+            region_index + 1,
+            style_index + 1,
+            agent,
+            text
+        ));
+    }
+
+    output.push_str("    </div>\n  </body>\n</tt>\n");
+    output
+}
+
+fn format_time(ms: i32, profile: Profile) -> String {
+    let ms = ms.max(0);
+    match profile {
+        Profile::Imsc1Text => format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            ms / 3600000,
+            (ms % 3600000) / 60000,
+            (ms % 60000) / 1000,
+            ms % 1000
+        ),
+        Profile::SmpteTt => format!("{}t", ms as i64 * EXPORT_TICK_RATE / 1000),
+    }
+}
+
+fn style_attributes(style: &CaptionStyle) -> String {
+    let cell = ass::PLAY_RES_Y / CELL_ROWS;
+    let mut attributes = format!(
+        " tts:color=\"{}\" tts:backgroundColor=\"{}\" tts:fontFamily=\"{}\" tts:fontSize=\"{}%\" tts:textAlign=\"{}\"",
+        ttml_color(&style.color),
+        ttml_color(&style.background),
+        escape_xml(&style.font_family),
+        (style.font_size as f32 / cell * 100.0).round(),
+        match style.alignment {
+            TextAlign::Left => "left",
+            TextAlign::Center => "center",
+            TextAlign::Right => "right",
+        }
+    );
+    if style.bold {
+        attributes.push_str(" tts:fontWeight=\"bold\"");
+    }
+    if style.italic {
+        attributes.push_str(" tts:fontStyle=\"italic\"");
+    }
+    if style.underline {
+        attributes.push_str(" tts:textDecoration=\"underline\"");
+    }
+    if style.outline_width > 0 {
+        attributes.push_str(&format!(
+            " tts:textOutline=\"{} {:.2}c\"",
+            ttml_color(style.outline_color.as_deref().unwrap_or("#000000")),
+            style.outline_width as f32 / cell
+        ));
+    }
+    attributes
+}
+
+fn region_attributes(position: &Position, width: Option<f32>) -> String {
+    let width = width.filter(|w| *w > 0.0 && *w <= 100.0).unwrap_or(80.0);
+    let (left, top, width, height, display_align) = match position {
+        Position::Bottom => ((100.0 - width) / 2.0, 10.0, width, 80.0, "after"),
+        Position::Top => ((100.0 - width) / 2.0, 10.0, width, 80.0, "before"),
+        Position::Middle => ((100.0 - width) / 2.0, 10.0, width, 80.0, "center"),
+        Position::Custom(x, y) => {
+            // Narrow the region so that it stays centred on x without leaving the frame.
+            let x = (*x as f32).clamp(0.0, 100.0);
+            let width = width.min(2.0 * x.min(100.0 - x)).max(1.0);
+            let left = (x - width / 2.0).clamp(0.0, 100.0 - width);
+            (left, (*y as f32 - 10.0).clamp(0.0, 80.0), width, 20.0, "center")
+        }
+    };
+    format!(
+        " tts:origin=\"{}% {}%\" tts:extent=\"{}% {}%\" tts:displayAlign=\"{}\"",
+        left, top, width, height, display_align
+    )
+}
+
+// TTML colours are #rrggbb or #rrggbbaa; anything else falls back to white.
+fn ttml_color(css: &str) -> String {
+    match parse_color(css) {
+        Some(color) => color.to_lowercase(),
+        None => String::from("#ffffff"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captioneditor::CaptionEditor;
+    use crate::structs::caption;
+
+    #[test]
+    fn round_trips_timing_text_and_speakers() {
+        let mut first = caption("1", 1000, 2500, "Hello there\nfriend");
+        first.speaker = Some(String::from("Alice & Bob"));
+        let second = caption("2", 3000, 4200, "<Second>");
+        let frame_rate = FrameRate::from_fps(25.0, false).unwrap();
+
+        for profile in [Profile::Imsc1Text, Profile::SmpteTt] {
+            let document = write(&[first.clone(), second.clone()], frame_rate, profile);
+            let parsed = parse(&document, &CaptionEditor::default_style()).unwrap();
+            assert_eq!(parsed.len(), 2);
+            assert_eq!((parsed[0].start_ms, parsed[0].end_ms), (1000, 2500));
+            assert_eq!(parsed[0].text, "Hello there\nfriend");
+            assert_eq!(parsed[0].speaker.as_deref(), Some("Alice & Bob"));
+            assert_eq!((parsed[1].start_ms, parsed[1].end_ms), (3000, 4200));
+            assert_eq!(parsed[1].text, "<Second>");
+            assert_eq!(parsed[1].speaker, None);
+        }
+    }
+
+    #[test]
+    fn resolves_agent_names() {
+        let document = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata">
+  <head><metadata>
+    <ttm:agent xml:id="narrator" type="person"><ttm:name type="alias">Nan</ttm:name><ttm:name type="full">Nancy Drew</ttm:name></ttm:agent>
+  </metadata></head>
+  <body><div>
+    <p begin="00:00:01.000" end="00:00:02.000" ttm:agent="narrator">One</p>
+    <p begin="00:00:03.000" end="00:00:04.000" ttm:agent="unknown">Two</p>
+  </div></body>
+</tt>"#;
+        let parsed = parse(document, &CaptionEditor::default_style()).unwrap();
+        assert_eq!(parsed[0].speaker.as_deref(), Some("Nancy Drew"));
+        assert_eq!(parsed[1].speaker.as_deref(), Some("unknown"));
+    }
+
+    #[test]
+    fn custom_region_stays_inside_frame() {
+        for x in [0, 1, 50, 99, 100] {
+            let attributes = region_attributes(&Position::Custom(x, 50), Some(80.0));
+            let origin = attributes.split('"').nth(1).unwrap();
+            let left: f32 = origin.split('%').next().unwrap().parse().unwrap();
+            assert!((0.0..=100.0).contains(&left), "x = {}: {}", x, attributes);
+        }
+    }
+
+    #[test]
+    fn round_trips_styling() {
+        let mut styled = caption("1", 1000, 2000, "Styled");
+        let style = &mut styled.style;
+        style.color = String::from("#FFD400");
+        style.background = String::from("#00000080");
+        (style.bold, style.italic, style.underline) = (true, true, true);
+        style.alignment = TextAlign::Left;
+        style.font_family = String::from("Verdana");
+        style.outline_width = 2;
+        style.outline_color = Some(String::from("#102030"));
+        let plain = caption("2", 3000, 4000, "Plain");
+
+        let document = write(&[styled.clone(), plain.clone()], FrameRate::default(), Profile::Imsc1Text);
+        let parsed = parse(&document, &CaptionEditor::default_style()).unwrap();
+        for (parsed, caption) in parsed.iter().zip([&styled, &plain]) {
+            let (got, want) = (&parsed.style, &caption.style);
+            assert_eq!((&got.color, &got.background), (&want.color, &want.background));
+            assert_eq!((got.bold, got.italic, got.underline), (want.bold, want.italic, want.underline));
+            assert_eq!(got.alignment, want.alignment);
+            assert_eq!(got.font_family, want.font_family);
+            assert_eq!(got.font_size, want.font_size);
+            assert_eq!((got.outline_width, &got.outline_color), (want.outline_width, &want.outline_color));
+        }
+    }
+
+    #[test]
+    fn round_trips_positions_through_regions() {
+        let positions = [Position::Top, Position::Middle, Position::Bottom, Position::Custom(30, 40)];
+        let captions: Vec<Caption> = positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| {
+                let mut caption = caption(&i.to_string(), i as i32 * 1000, i as i32 * 1000 + 900, "Placed");
+                caption.style.position = position.clone();
+                caption
+            })
+            .collect();
+
+        let document = write(&captions, FrameRate::default(), Profile::Imsc1Text);
+        let parsed = parse(&document, &CaptionEditor::default_style()).unwrap();
+        let parsed_positions: Vec<Position> = parsed.iter().map(|c| c.style.position.clone()).collect();
+        assert_eq!(parsed_positions, positions);
+    }
+
+    #[test]
+    fn end_display_align_is_bottom_like_after() {
+        for display_align in ["after", "end"] {
+            let document = format!(
+                r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling">
+  <head><layout><region xml:id="r1" tts:origin="10% 70%" tts:extent="80% 20%" tts:displayAlign="{}"/></layout></head>
+  <body><div><p begin="00:00:01.000" end="00:00:02.000" region="r1">One</p></div></body>
+</tt>"#,
+                display_align
+            );
+            let parsed = parse(&document, &CaptionEditor::default_style()).unwrap();
+            assert_eq!(parsed[0].style.position, Position::Bottom, "{}", display_align);
+        }
+    }
+}
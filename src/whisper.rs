@@ -0,0 +1,183 @@
+use crate::srt;
+use crate::structs::{Caption, CaptionStyle, Word};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+// Parses whisper.cpp full JSON output (`-ojf`). Subword tokens are joined into
+// words: a token starting with a space starts a new word, and punctuation stays
+// with the word before it. Word and caption confidence are the mean token
+// probability `p`. Special tokens such as [_BEG_] and [_TT_150] are dropped.
+// Returned captions have empty ids; the editor assigns them.
+pub fn parse(content: &str, base: &CaptionStyle) -> Result<Vec<Caption>, JsValue> {
+    let root: Value = serde_json::from_str(content).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let segments = root
+        .get("transcription")
+        .or_else(|| root.get("segments"))
+        .and_then(Value::as_array)
+        .ok_or_else(|| JsValue::from_str("Invalid whisper.cpp JSON: missing transcription"))?;
+
+    let mut captions = Vec::new();
+
+    for segment in segments {
+        let Some((start_ms, end_ms)) = span(segment) else {
+            log::warn!("Skipping whisper segment without timing: {:?}", segment.get("text"));
+            continue;
+        };
+
+        // Each word keeps the probabilities of its tokens until it is complete.
+        let mut words: Vec<(Word, Vec<f32>)> = Vec::new();
+        let mut probabilities = Vec::new();
+        let mut timed = false;
+
+        for token in segment.get("tokens").and_then(Value::as_array).into_iter().flatten() {
+            let Some(text) = token.get("text").and_then(Value::as_str) else {
+                continue;
+            };
+            if is_special(text) {
+                continue;
+            }
+            let p = token.get("p").and_then(Value::as_f64).unwrap_or(1.0) as f32;
+            probabilities.push(p);
+
+            let piece = text.trim();
+            if piece.is_empty() {
+                continue;
+            }
+            let (token_start, token_end) = match span(token) {
+                Some(times) => {
+                    timed = true;
+                    times
+                }
+                None => (start_ms, end_ms),
+            };
+
+            let punctuation = piece.chars().all(|c| c.is_ascii_punctuation());
+            let continues_word = !text.starts_with(' ') || punctuation;
+            match words.last_mut() {
+                Some((word, word_p)) if continues_word => {
+                    word.text.push_str(piece);
+                    word.end_ms = token_end.max(word.end_ms);
+                    word_p.push(p);
+                }
+                _ => words.push((
+                    Word { text: piece.to_string(), start_ms: token_start, end_ms: token_end, confidence: p },
+                    vec![p],
+                )),
+            }
+        }
+
+        let words: Vec<Word> = if timed {
+            words
+                .into_iter()
+                .map(|(mut word, word_p)| {
+                    word.confidence = mean(&word_p).unwrap_or(1.0);
+                    word
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let text = match segment.get("text").and_then(Value::as_str) {
+            Some(text) => strip_special(text),
+            None => words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" "),
+        };
+        if text.is_empty() {
+            continue;
+        }
+
+        captions.push(Caption {
+            id: String::new(),
+            start_ms,
+            end_ms,
+            text,
+            speaker: None,
+            confidence: mean(&probabilities).unwrap_or(1.0),
+            style: base.clone(),
+            words,
+            track: None,
+        });
+    }
+
+    captions.sort_by_key(|c| c.start_ms);
+    Ok(captions)
+}
+
+// Reads a start/end pair from `offsets` (ms), `t0`/`t1` (10 ms units) or
+// `timestamps` ("HH:MM:SS,mmm").
+fn span(value: &Value) -> Option<(i32, i32)> {
+    if let Some(offsets) = value.get("offsets") {
+        let from = offsets.get("from").and_then(Value::as_i64)?;
+        let to = offsets.get("to").and_then(Value::as_i64)?;
+        return Some((from as i32, to as i32));
+    }
+    if let (Some(t0), Some(t1)) = (value.get("t0").and_then(Value::as_i64), value.get("t1").and_then(Value::as_i64)) {
+        return Some((t0 as i32 * 10, t1 as i32 * 10));
+    }
+    let timestamps = value.get("timestamps")?;
+    let from = srt::parse_timestamp(timestamps.get("from").and_then(Value::as_str)?).ok()?;
+    let to = srt::parse_timestamp(timestamps.get("to").and_then(Value::as_str)?).ok()?;
+    Some((from, to))
+}
+
+// Control tokens: [_BEG_], [_SOT_], [_TT_150] and the like, or <|endoftext|>.
+fn is_special(text: &str) -> bool {
+    let text = text.trim();
+    (text.starts_with("[_") && text.ends_with(']')) || (text.starts_with("<|") && text.ends_with("|>"))
+}
+
+fn strip_special(text: &str) -> String {
+    text.split_whitespace().filter(|piece| !is_special(piece)).collect::<Vec<_>>().join(" ")
+}
+
+fn mean(values: &[f32]) -> Option<f32> {
+    (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captioneditor::CaptionEditor;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn parses_sample_transcription() {
+        let content = include_str!("../tests/fixtures/whisper_cpp.json");
+        let captions = parse(content, &CaptionEditor::default_style()).unwrap();
+        assert_eq!(captions.len(), 2);
+
+        // Without token timings there are no words.
+        let intro = &captions[0];
+        assert_eq!((intro.start_ms, intro.end_ms, intro.text.as_str()), (0, 900, "Intro"));
+        assert!(intro.words.is_empty());
+        assert_near(intro.confidence, 0.6);
+
+        let hello = &captions[1];
+        assert_eq!((hello.start_ms, hello.end_ms, hello.text.as_str()), (1000, 3000, "Hello, world."));
+        let words: Vec<_> = hello.words.iter().map(|w| (w.text.as_str(), w.start_ms, w.end_ms)).collect();
+        assert_eq!(words, [("Hello,", 1000, 1700), ("world.", 1900, 2800)]);
+        assert_near(hello.words[0].confidence, 0.85);
+        assert_near(hello.words[1].confidence, 0.8666667);
+        assert_near(hello.confidence, 0.86);
+    }
+
+    #[test]
+    fn reads_each_timing_style() {
+        let content = r#"{"segments": [
+            {"t0": 150, "t1": 275, "text": "centiseconds"},
+            {"timestamps": {"from": "00:01:00,250", "to": "00:01:01,000"}, "text": "timestamps"}
+        ]}"#;
+        let captions = parse(content, &CaptionEditor::default_style()).unwrap();
+        assert_eq!((captions[0].start_ms, captions[0].end_ms), (1500, 2750));
+        assert_eq!((captions[1].start_ms, captions[1].end_ms), (60_250, 61_000));
+    }
+
+    #[test]
+    fn detects_sample_transcription() {
+        let content = include_str!("../tests/fixtures/whisper_cpp.json");
+        assert_eq!(crate::detect::detect_format(content), Some("whisper"));
+    }
+}
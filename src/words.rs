@@ -0,0 +1,309 @@
+use crate::structs::{Caption, Word};
+
+// True when the words spell out the caption text token for token.
+pub fn matches_text(words: &[Word], text: &str) -> bool {
+    !words.is_empty() && words.iter().flat_map(|w| w.text.split_whitespace()).eq(text.split_whitespace())
+}
+
+// Replaces a caption's text and realigns its word timings to the new tokens.
+// Words that survive the edit keep their timing and confidence; new or changed
+// words share out the time of what they replaced and are treated as confirmed.
+pub fn set_text(caption: &mut Caption, text: String) {
+    if !caption.words.is_empty() {
+        caption.words = realign(&caption.words, &text, caption.start_ms, caption.end_ms);
+    }
+    caption.text = text;
+}
+
+fn realign(words: &[Word], text: &str, start_ms: i32, end_ms: i32) -> Vec<Word> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let old_keys: Vec<String> = words.iter().map(|w| match_key(&w.text)).collect();
+    let new_keys: Vec<String> = tokens.iter().map(|t| match_key(t)).collect();
+
+    // Longest common subsequence of tokens, compared without case or punctuation.
+    let (n, m) = (old_keys.len(), new_keys.len());
+    let mut lcs = vec![vec![0u16; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_keys[i] == new_keys[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut anchors = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old_keys[i] == new_keys[j] {
+            anchors.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    anchors.push((n, m));
+
+    let mut result = Vec::with_capacity(m);
+    let (mut old_from, mut new_from) = (0, 0);
+    for (old_to, new_to) in anchors {
+        // Unmatched tokens between two anchors take over the span of the
+        // unmatched old words there, or the gap between the anchors.
+        if new_from < new_to {
+            let (span_start, span_end) = if old_from < old_to {
+                (words[old_from].start_ms, words[old_to - 1].end_ms)
+            } else {
+                (
+                    old_from.checked_sub(1).map_or(start_ms, |i| words[i].end_ms),
+                    words.get(old_to).map_or(end_ms, |w| w.start_ms),
+                )
+            };
+            distribute(&tokens[new_from..new_to], span_start, span_end.max(span_start), &mut result);
+        }
+        if let (Some(word), Some(token)) = (words.get(old_to), tokens.get(new_to)) {
+            result.push(Word { text: token.to_string(), ..word.clone() });
+        }
+        old_from = old_to + 1;
+        new_from = new_to + 1;
+    }
+
+    result
+}
+
+// Shares a time span between tokens in proportion to their length.
+fn distribute(tokens: &[&str], start_ms: i32, end_ms: i32, out: &mut Vec<Word>) {
+    let total: usize = tokens.iter().map(|t| t.chars().count()).sum::<usize>().max(1);
+    let mut elapsed = 0;
+    for token in tokens {
+        let word_start = start_ms + ((end_ms - start_ms) as i64 * elapsed as i64 / total as i64) as i32;
+        elapsed += token.chars().count();
+        let word_end = start_ms + ((end_ms - start_ms) as i64 * elapsed as i64 / total as i64) as i32;
+        out.push(Word { text: token.to_string(), start_ms: word_start, end_ms: word_end, confidence: 1.0 });
+    }
+}
+
+fn match_key(token: &str) -> String {
+    let key: String = token.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+    if key.is_empty() { token.to_string() } else { key }
+}
+
+// Maps word timings from one caption span onto another, e.g. after a trim or move.
+pub fn retime(words: &mut [Word], from: (i32, i32), to: (i32, i32)) {
+    let (from_len, to_len) = ((from.1 - from.0) as i64, (to.1 - to.0) as i64);
+    let map = |t: i32| {
+        if from_len <= 0 {
+            t - from.0 + to.0
+        } else {
+            to.0 + ((t - from.0) as i64 * to_len / from_len) as i32
+        }
+    };
+    for word in words {
+        word.start_ms = map(word.start_ms);
+        word.end_ms = map(word.end_ms);
+    }
+}
+
+// Splits words at a time: words starting before `split_ms` go first, trimmed to end by it.
+pub fn split_at(words: &[Word], split_ms: i32) -> (Vec<Word>, Vec<Word>) {
+    let (mut first, second): (Vec<Word>, Vec<Word>) = words.iter().cloned().partition(|w| w.start_ms < split_ms);
+    for word in &mut first {
+        word.end_ms = word.end_ms.min(split_ms);
+    }
+    (first, second)
+}
+
+// Splits word timings where the caption text is cut at byte `offset`, returning
+// both halves and the time of the cut. A word cut in two is divided by character
+// count. Returns None when the words do not match the text.
+pub fn split_at_offset(words: &[Word], text: &str, offset: usize) -> Option<(Vec<Word>, Vec<Word>, i32)> {
+    let tokens = token_spans(text);
+    if tokens.len() != words.len() || !matches_text(words, text) {
+        return None;
+    }
+
+    let (mut first, mut second, mut split_ms) = (Vec::new(), Vec::new(), None);
+    for ((start, token), word) in tokens.into_iter().zip(words) {
+        if start + token.len() <= offset {
+            first.push(word.clone());
+        } else if start >= offset {
+            split_ms.get_or_insert(word.start_ms);
+            second.push(word.clone());
+        } else {
+            let (head, tail) = token.split_at(offset - start);
+            let at = word.start_ms
+                + ((word.end_ms - word.start_ms) as i64 * head.chars().count() as i64 / token.chars().count() as i64)
+                    as i32;
+            first.push(Word { text: head.to_string(), end_ms: at, ..word.clone() });
+            second.push(Word { text: tail.to_string(), start_ms: at, ..word.clone() });
+            split_ms = Some(at);
+        }
+    }
+    Some((first, second, split_ms?))
+}
+
+// Whitespace-separated tokens with their byte offsets in the text.
+pub fn token_spans(text: &str) -> Vec<(usize, &str)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, &text[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    spans
+}
+
+// Words that belong with the word after them: articles, determiners and titles.
+const BINDING_WORDS: [&str; 16] = [
+    "a", "an", "the", "this", "these", "those", "my", "your", "his", "her", "its", "our", "their", "mr", "mrs", "dr",
+];
+
+// Words that usually open a clause, so a cue or line may start with them.
+const CLAUSE_WORDS: [&str; 14] = [
+    "and", "but", "or", "so", "because", "which", "who", "when", "where", "while", "if", "that", "until", "although",
+];
+
+// How much each kind of boundary between two tokens favours (the first three)
+// or discourages (`binding`) a break there.
+pub struct BreakWeights {
+    pub sentence_end: f32,
+    pub clause_end: f32, // after a comma, semicolon, colon or dash
+    pub clause_word: f32,
+    pub binding: f32,
+}
+
+// The cost of breaking between two tokens; negative where a break reads naturally.
+pub fn break_cost(before: &str, after: &str, weights: &BreakWeights) -> f32 {
+    let key = |word: &str| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+
+    let mut cost = 0.0;
+    if before.ends_with(['.', '?', '!']) {
+        cost -= weights.sentence_end;
+    } else if before.ends_with([',', ';', ':', '\u{2014}']) {
+        cost -= weights.clause_end;
+    }
+    if CLAUSE_WORDS.contains(&key(after).as_str()) {
+        cost -= weights.clause_word;
+    }
+    if BINDING_WORDS.contains(&key(before).as_str()) {
+        cost += weights.binding;
+    }
+    cost
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT: BreakWeights = BreakWeights { sentence_end: 4.0, clause_end: 2.0, clause_word: 1.0, binding: 8.0 };
+
+    #[test]
+    fn scores_breaks_by_the_boundary_between_tokens() {
+        assert_eq!(break_cost("here.", "Then", &UNIT), -4.0);
+        assert_eq!(break_cost("here,", "and", &UNIT), -3.0);
+        assert_eq!(break_cost("said", "that", &UNIT), -1.0);
+        assert_eq!(break_cost("The", "dog", &UNIT), 8.0);
+        assert_eq!(break_cost("(Mrs.", "Jones", &UNIT), 4.0);
+        assert_eq!(break_cost("dog", "barked", &UNIT), 0.0);
+    }
+
+    fn word(text: &str, start_ms: i32, end_ms: i32) -> Word {
+        Word { text: text.to_string(), start_ms, end_ms, confidence: 0.5 }
+    }
+
+    fn timings(words: &[Word]) -> Vec<(&str, i32, i32)> {
+        words.iter().map(|w| (w.text.as_str(), w.start_ms, w.end_ms)).collect()
+    }
+
+    fn sample() -> Caption {
+        let mut caption = crate::structs::caption("1", 0, 2400, "the quick brown fox jumps");
+        caption.words = vec![
+            word("the", 0, 200),
+            word("quick", 300, 600),
+            word("brown", 700, 1000),
+            word("fox", 1100, 1400),
+            word("jumps", 1500, 2000),
+        ];
+        caption
+    }
+
+    #[test]
+    fn set_text_keeps_timings_of_surviving_words() {
+        let mut caption = sample();
+        // "very" is inserted mid-text, "brown" removed and "high" added at the end.
+        set_text(&mut caption, String::from("The very quick fox, jumps high"));
+        assert_eq!(caption.text, "The very quick fox, jumps high");
+        assert_eq!(
+            timings(&caption.words),
+            [
+                ("The", 0, 200),
+                ("very", 200, 300),
+                ("quick", 300, 600),
+                ("fox,", 1100, 1400),
+                ("jumps", 1500, 2000),
+                ("high", 2000, 2400),
+            ]
+        );
+        let confidences: Vec<f32> = caption.words.iter().map(|w| w.confidence).collect();
+        assert_eq!(confidences, [0.5, 1.0, 0.5, 0.5, 0.5, 1.0]);
+        assert!(matches_text(&caption.words, &caption.text));
+    }
+
+    #[test]
+    fn set_text_shares_a_replaced_word_between_its_replacements() {
+        let mut caption = sample();
+        set_text(&mut caption, String::from("the quick reddish brownish fox jumps"));
+        assert_eq!(timings(&caption.words)[2..4], [("reddish", 700, 840), ("brownish", 840, 1000)]);
+
+        // Without word timings only the text changes.
+        let mut plain = crate::structs::caption("2", 0, 1000, "old");
+        set_text(&mut plain, String::from("new text"));
+        assert_eq!(plain.text, "new text");
+        assert!(plain.words.is_empty());
+    }
+
+    #[test]
+    fn retime_maps_words_onto_the_new_span() {
+        let mut words = sample().words;
+        retime(&mut words[..2], (0, 2400), (1000, 2200));
+        assert_eq!(timings(&words[..2]), [("the", 1000, 1100), ("quick", 1150, 1300)]);
+
+        // A zero-length span can only be shifted.
+        let mut words = vec![word("hi", 500, 500)];
+        retime(&mut words, (500, 500), (800, 900));
+        assert_eq!(timings(&words), [("hi", 800, 800)]);
+    }
+
+    #[test]
+    fn splits_words_at_a_time() {
+        let (first, second) = split_at(&sample().words, 500);
+        assert_eq!(timings(&first), [("the", 0, 200), ("quick", 300, 500)]);
+        assert_eq!(timings(&second)[0], ("brown", 700, 1000));
+        assert_eq!(second.len(), 3);
+    }
+
+    #[test]
+    fn splits_words_at_a_text_offset() {
+        let caption = sample();
+
+        // Between words, the cut falls at the start of the next word.
+        let (first, second, split_ms) = split_at_offset(&caption.words, &caption.text, 9).unwrap();
+        assert_eq!((first.len(), second.len(), split_ms), (2, 3, 700));
+
+        // Inside a word, its time is shared by character count.
+        let (first, second, split_ms) = split_at_offset(&caption.words, &caption.text, 6).unwrap();
+        assert_eq!(timings(&first), [("the", 0, 200), ("qu", 300, 420)]);
+        assert_eq!(timings(&second)[..2], [("ick", 420, 600), ("brown", 700, 1000)]);
+        assert_eq!(split_ms, 420);
+
+        assert_eq!(split_at_offset(&caption.words, "the quick brown fox", 9), None);
+    }
+}
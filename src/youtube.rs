@@ -0,0 +1,265 @@
+use crate::srt;
+use crate::structs::{Caption, CaptionStyle, Word};
+use crate::words;
+use serde_json::{Value, json};
+use wasm_bindgen::prelude::*;
+
+// Parses SubViewer (.sbv) as downloaded from YouTube: blocks of
+// "H:MM:SS.mmm,H:MM:SS.mmm" followed by the caption text.
+// Returned captions have empty ids; the editor assigns them.
+pub fn parse_sbv(content: &str, base: &CaptionStyle) -> Result<Vec<Caption>, JsValue> {
+    let normalized = content.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+
+    let mut captions = Vec::new();
+    let mut lines = normalized.lines().peekable();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut text = Vec::new();
+        while let Some(next) = lines.next_if(|l| !l.trim().is_empty()) {
+            text.push(next.trim_end());
+        }
+
+        let times = line
+            .split_once(',')
+            .and_then(|(start, end)| Some((srt::parse_timestamp(start).ok()?, srt::parse_timestamp(end).ok()?)));
+        let Some((start_ms, end_ms)) = times else {
+            log::warn!("Skipping SBV block with an invalid timing line: {:?}", line);
+            continue;
+        };
+        if text.is_empty() {
+            continue;
+        }
+
+        captions.push(Caption {
+            id: String::new(),
+            start_ms,
+            end_ms,
+            text: text.join("\n"),
+            speaker: None,
+            confidence: 1.0,
+            style: base.clone(),
+            words: Vec::new(),
+            track: None,
+        });
+    }
+
+    Ok(captions)
+}
+
+pub fn write_sbv(captions: &[Caption]) -> String {
+    let mut output = String::new();
+    for caption in captions {
+        output.push_str(&format!(
+            "{},{}\n{}\n\n",
+            format_sbv_timestamp(caption.start_ms),
+            format_sbv_timestamp(caption.end_ms),
+            caption.text
+        ));
+    }
+    output
+}
+
+fn format_sbv_timestamp(ms: i32) -> String {
+    let ms = ms.max(0);
+    format!("{}:{:02}:{:02}.{:03}", ms / 3600000, (ms / 60000) % 60, (ms / 1000) % 60, ms % 1000)
+}
+
+// Parses YouTube's json3 timedtext format. Each event's `segs` become words,
+// timed from `tOffsetMs` to the next segment (or the end of the event).
+// Auto-captions roll up, so their events overlap; those are cut at the next
+// event's start to give one caption on screen at a time.
+pub fn parse_json3(content: &str, base: &CaptionStyle) -> Result<Vec<Caption>, JsValue> {
+    let root: Value = serde_json::from_str(content).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let events = root
+        .get("events")
+        .and_then(Value::as_array)
+        .ok_or_else(|| JsValue::from_str("Invalid json3 file: missing events"))?;
+
+    let mut captions: Vec<Caption> = Vec::new();
+    let mut automatic = false;
+
+    for event in events {
+        let Some(segs) = event.get("segs").and_then(Value::as_array) else {
+            continue; // window definitions carry no text
+        };
+        let start_ms = event.get("tStartMs").and_then(Value::as_i64).unwrap_or(0) as i32;
+        let end_ms = start_ms + event.get("dDurationMs").and_then(Value::as_i64).unwrap_or(0) as i32;
+
+        let text: String = segs.iter().filter_map(|s| s.get("utf8").and_then(Value::as_str)).collect();
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut words: Vec<Word> = Vec::new();
+        for seg in segs {
+            let Some(utf8) = seg.get("utf8").and_then(Value::as_str) else {
+                continue;
+            };
+            let word_start = start_ms + seg.get("tOffsetMs").and_then(Value::as_i64).unwrap_or(0) as i32;
+            if let Some(previous) = words.last_mut() {
+                previous.end_ms = word_start.max(previous.start_ms);
+            }
+            if utf8.trim().is_empty() {
+                continue;
+            }
+            // acAsrConf is only present on auto-captions, on a 0-255 scale where 0 means unknown.
+            let confidence = match seg.get("acAsrConf").and_then(Value::as_f64) {
+                Some(conf) => {
+                    automatic = true;
+                    if conf > 0.0 { (conf / 255.0) as f32 } else { 1.0 }
+                }
+                None => 1.0,
+            };
+            words.push(Word { text: utf8.trim().to_string(), start_ms: word_start, end_ms, confidence });
+        }
+        // Segments without timing or ASR data are styling runs, not words.
+        if !segs.iter().any(|s| s.get("tOffsetMs").is_some() || s.get("acAsrConf").is_some()) {
+            words.clear();
+        }
+
+        let confidence = if words.is_empty() {
+            1.0
+        } else {
+            words.iter().map(|w| w.confidence).sum::<f32>() / words.len() as f32
+        };
+
+        captions.push(Caption {
+            id: String::new(),
+            start_ms,
+            end_ms,
+            text,
+            speaker: None,
+            confidence,
+            style: base.clone(),
+            words,
+            track: None,
+        });
+    }
+
+    captions.sort_by_key(|c| c.start_ms);
+    if automatic {
+        for i in 1..captions.len() {
+            let next_start = captions[i].start_ms;
+            let previous = &mut captions[i - 1];
+            if previous.end_ms > next_start && next_start > previous.start_ms {
+                previous.end_ms = next_start;
+                for word in &mut previous.words {
+                    word.end_ms = word.end_ms.min(next_start);
+                }
+            }
+        }
+    }
+
+    Ok(captions)
+}
+
+// Writes json3 with one event per caption. Word timings are written as segments
+// when they still match the caption text.
+pub fn write_json3(captions: &[Caption]) -> String {
+    let events: Vec<Value> = captions
+        .iter()
+        .map(|caption| {
+            let segs: Vec<Value> = if words::matches_text(&caption.words, &caption.text) {
+                caption
+                    .words
+                    .iter()
+                    .enumerate()
+                    .map(|(i, word)| {
+                        let utf8 = if i == 0 { word.text.clone() } else { format!(" {}", word.text) };
+                        let offset = (word.start_ms - caption.start_ms).max(0);
+                        if offset == 0 { json!({ "utf8": utf8 }) } else { json!({ "utf8": utf8, "tOffsetMs": offset }) }
+                    })
+                    .collect()
+            } else {
+                vec![json!({ "utf8": caption.text })]
+            };
+
+            json!({
+                "tStartMs": caption.start_ms.max(0),
+                "dDurationMs": (caption.end_ms - caption.start_ms).max(0),
+                "segs": segs,
+            })
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&json!({ "wireMagic": "pb3", "events": events })).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captioneditor::CaptionEditor;
+    use crate::structs::caption;
+
+    fn word(text: &str, start_ms: i32, end_ms: i32) -> Word {
+        Word { text: text.to_string(), start_ms, end_ms, confidence: 1.0 }
+    }
+
+    #[test]
+    fn parses_sbv_and_skips_bad_blocks() {
+        let content = "0:00:01.000,0:00:02.500\r\nHello\r\nthere\r\n\r\nnot a time\r\nSkipped\r\n\r\n\
+            1:02:03.040,1:02:04.000\r\n>> Second\r\n";
+        let captions = parse_sbv(content, &CaptionEditor::default_style()).unwrap();
+        assert_eq!(captions.len(), 2);
+        assert_eq!((captions[0].start_ms, captions[0].end_ms), (1000, 2500));
+        assert_eq!(captions[0].text, "Hello\nthere");
+        assert_eq!((captions[1].start_ms, captions[1].end_ms), (3_723_040, 3_724_000));
+        assert_eq!(captions[1].text, ">> Second");
+    }
+
+    #[test]
+    fn round_trips_sbv() {
+        let captions = [caption("1", 0, 1500, "One\ntwo"), caption("2", 3_600_001, 3_601_000, "Three")];
+        let written = write_sbv(&captions);
+        assert!(written.starts_with("0:00:00.000,0:00:01.500\nOne\ntwo\n\n1:00:00.001,1:00:01.000\n"));
+
+        let parsed = parse_sbv(&written, &CaptionEditor::default_style()).unwrap();
+        for (parsed, caption) in parsed.iter().zip(&captions) {
+            assert_eq!((parsed.start_ms, parsed.end_ms), (caption.start_ms, caption.end_ms));
+            assert_eq!(parsed.text, caption.text);
+        }
+    }
+
+    #[test]
+    fn parses_rolling_auto_captions_into_timed_words() {
+        let content = r#"{"wireMagic": "pb3", "events": [
+            {"tStartMs": 0, "dDurationMs": 5000, "id": 1, "wpWinPosId": 1},
+            {"tStartMs": 1000, "dDurationMs": 4000, "wWinId": 1, "segs": [
+                {"utf8": "hello", "acAsrConf": 255}, {"utf8": " world", "tOffsetMs": 600, "acAsrConf": 0}]},
+            {"tStartMs": 2500, "dDurationMs": 10, "wWinId": 1, "aAppend": 1, "segs": [{"utf8": "\n"}]},
+            {"tStartMs": 3000, "dDurationMs": 2000, "wWinId": 1, "segs": [
+                {"utf8": "again", "acAsrConf": 51}]}
+        ]}"#;
+        let captions = parse_json3(content, &CaptionEditor::default_style()).unwrap();
+
+        assert_eq!(captions.len(), 2);
+        let first = &captions[0];
+        assert_eq!((first.start_ms, first.end_ms), (1000, 3000));
+        assert_eq!(first.text, "hello world");
+        assert_eq!(first.words, [word("hello", 1000, 1600), word("world", 1600, 3000)]);
+        let second = &captions[1];
+        assert_eq!((second.start_ms, second.end_ms), (3000, 5000));
+        assert_eq!(second.words[0].confidence, 0.2);
+        assert_eq!(second.confidence, 0.2);
+    }
+
+    #[test]
+    fn round_trips_json3_with_word_timings() {
+        let mut timed = caption("1", 1000, 3000, "hello big world");
+        timed.words = vec![word("hello", 1000, 1500), word("big", 1500, 2200), word("world", 2200, 3000)];
+        let plain = caption("2", 4000, 5000, "Styled\nline");
+
+        let written = write_json3(&[timed.clone(), plain.clone()]);
+        let parsed = parse_json3(&written, &CaptionEditor::default_style()).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!((parsed[0].start_ms, parsed[0].end_ms, &parsed[0].text), (1000, 3000, &timed.text));
+        assert_eq!(parsed[0].words, timed.words);
+        assert_eq!((parsed[1].start_ms, parsed[1].end_ms, &parsed[1].text), (4000, 5000, &plain.text));
+        assert!(parsed[1].words.is_empty());
+    }
+}